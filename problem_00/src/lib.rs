@@ -0,0 +1,8 @@
+mod metrics;
+pub mod server;
+
+pub const MAX_CONNECTIONS: usize = 100;
+pub const DEFAULT_PORT: u16 = 8080;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,16 @@
+use protohackers_core::metrics::{Counter, Registry};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Metrics {
+    pub(crate) bytes_received: Counter,
+    pub(crate) bytes_sent: Counter,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Metrics {
+        Metrics {
+            bytes_received: registry.counter("bytes_received_total", "Bytes read from clients."),
+            bytes_sent: registry.counter("bytes_sent_total", "Bytes written to clients."),
+        }
+    }
+}
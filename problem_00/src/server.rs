@@ -0,0 +1,73 @@
+use crate::metrics::Metrics;
+
+use protohackers_core::{metrics::Registry, ConnectionHandler, Server, ServerConfig, Shutdown};
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{error, info};
+
+/// Echoes back everything a client sends.
+struct SmokeTest {
+    metrics: Metrics,
+}
+
+pub async fn run(
+    listener: TcpListener,
+    config: ServerConfig,
+    shutdown: impl Future,
+) -> crate::Result<()> {
+    info!("Start TCP server");
+
+    let registry = Registry::new("smoke_test");
+    let smoke_test = SmokeTest {
+        metrics: Metrics::new(&registry),
+    };
+
+    Server::new(listener, smoke_test)
+        .max_connections(config.max_connections)
+        .metrics(registry, config.bind_metrics().await?)
+        .run(shutdown)
+        .await
+}
+
+impl ConnectionHandler for SmokeTest {
+    async fn handle(
+        &self,
+        mut socket: TcpStream,
+        _address: SocketAddr,
+        mut shutdown: Shutdown,
+    ) -> crate::Result<()> {
+        let mut buf = [0; 1024];
+
+        loop {
+            let res = tokio::select! {
+                res = socket.read(&mut buf) => res,
+                _ = shutdown.recv() => return Ok(()),
+            };
+
+            let n = match res {
+                Ok(0) => {
+                    info!("Receiving echo: 0");
+                    return Ok(());
+                }
+                Ok(n) => {
+                    info!("Receiving echo: {}", n);
+                    self.metrics.bytes_received.inc_by(n as u64);
+                    n
+                }
+                Err(e) => {
+                    error!("failed to read from socket; err = {:?}", e);
+                    return Err(e.into());
+                }
+            };
+
+            if let Err(e) = socket.write_all(&buf[0..n]).await {
+                error!("failed to write to socket; err = {:?}", e);
+                return Err(e.into());
+            }
+
+            self.metrics.bytes_sent.inc_by(n as u64);
+        }
+    }
+}
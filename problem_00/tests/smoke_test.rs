@@ -0,0 +1,50 @@
+use problem_00::{server, DEFAULT_PORT, MAX_CONNECTIONS};
+use protohackers_core::test_support::{BinaryClient, TestServer};
+use protohackers_core::ServerConfig;
+
+async fn start() -> TestServer {
+    TestServer::start(|listener, shutdown| {
+        server::run(
+            listener,
+            ServerConfig::new(DEFAULT_PORT, MAX_CONNECTIONS),
+            shutdown,
+        )
+    })
+    .await
+}
+
+#[tokio::test]
+async fn echoes_everything_until_the_client_closes() {
+    let server = start().await;
+    let mut client = BinaryClient::connect(server.address()).await;
+
+    client.send(b"hello").await;
+    client.expect(b"hello").await;
+
+    let payload: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+    client.send(&payload).await;
+    client.expect(&payload).await;
+
+    client.close().await;
+    client.expect_closed().await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn handles_clients_concurrently() {
+    let server = start().await;
+
+    let mut clients = Vec::new();
+    for _ in 0..5 {
+        clients.push(BinaryClient::connect(server.address()).await);
+    }
+
+    for (i, client) in clients.iter_mut().enumerate().rev() {
+        let message = format!("client {i}");
+        client.send(message.as_bytes()).await;
+        client.expect(message.as_bytes()).await;
+    }
+
+    server.stop().await.unwrap();
+}
@@ -0,0 +1,22 @@
+use protohackers_core::metrics::{Counter, CounterVec, Registry};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Metrics {
+    pub(crate) requests: CounterVec,
+    pub(crate) bytes_received: Counter,
+    pub(crate) bytes_sent: Counter,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Metrics {
+        Metrics {
+            requests: registry.counter_vec(
+                "requests_total",
+                "Requests received, valid or malformed.",
+                "result",
+            ),
+            bytes_received: registry.counter("bytes_received_total", "Bytes read from clients."),
+            bytes_sent: registry.counter("bytes_sent_total", "Bytes written to clients."),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::future::Future;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+
+use primes::is_prime;
+use protohackers_core::{metrics::Registry, ConnectionHandler, Server, ServerConfig, Shutdown};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{error, info};
+
+use crate::metrics::Metrics;
+
+const IS_PRIME: &str = "isPrime";
+const MAL_FORMAT: &str = "}mal";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Request {
+    method: String,
+    number: serde_json::value::Number,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Response {
+    method: String,
+    prime: bool,
+}
+
+/// Answers `isPrime` requests, one JSON object per line.
+struct PrimeTime {
+    metrics: Metrics,
+}
+
+pub async fn run(
+    listener: TcpListener,
+    config: ServerConfig,
+    shutdown: impl Future,
+) -> crate::Result<()> {
+    info!("Start TCP server");
+
+    let registry = Registry::new("prime_time");
+    let prime_time = PrimeTime {
+        metrics: Metrics::new(&registry),
+    };
+
+    Server::new(listener, prime_time)
+        .max_connections(config.max_connections)
+        .metrics(registry, config.bind_metrics().await?)
+        .run(shutdown)
+        .await
+}
+
+impl ConnectionHandler for PrimeTime {
+    async fn handle(
+        &self,
+        socket: TcpStream,
+        _address: SocketAddr,
+        shutdown: Shutdown,
+    ) -> crate::Result<()> {
+        info!("Handle incoming request");
+        handle_request(socket, shutdown, &self.metrics).await
+    }
+}
+
+async fn handle_request(
+    mut socket: TcpStream,
+    mut shutdown: Shutdown,
+    metrics: &Metrics,
+) -> crate::Result<()> {
+    let (read, mut write) = socket.split();
+
+    let mut buf: Vec<u8> = Vec::new();
+    let mut reader = BufReader::new(read);
+
+    loop {
+        let bytes = tokio::select! {
+            res = reader.read_until(b'\n', &mut buf) => res?,
+            _ = shutdown.recv() => return Ok(()),
+        };
+
+        if bytes == 0 {
+            info!("0 bytes sent");
+            return Ok(());
+        }
+
+        metrics.bytes_received.inc_by(bytes as u64);
+
+        let response = match validate_request(&buf) {
+            Ok(m) => {
+                info!("Valid request");
+                metrics.requests.with("valid").inc();
+                m
+            }
+            Err(_) => {
+                error!("Not valid request");
+                metrics.requests.with("malformed").inc();
+                MAL_FORMAT.to_string()
+            }
+        };
+
+        write.write_all(response.as_bytes()).await?;
+        write.write_all(b"\n").await?;
+        metrics.bytes_sent.inc_by(response.len() as u64 + 1);
+        write.flush().await?;
+        buf.clear();
+    }
+}
+
+fn validate_request(message: &[u8]) -> Result<String, std::io::Error> {
+    match serde_json::from_slice::<Request>(message) {
+        Ok(m) => {
+            let possible_prime = match m.number.to_string().parse::<u64>() {
+                Ok(n) => n,
+                Err(_) => {
+                    error!("Not a valid number for a prime candidate: {}", m.number);
+                    return Ok(serde_json::to_string(&Response {
+                        method: IS_PRIME.to_owned(),
+                        prime: false,
+                    })
+                    .unwrap());
+                }
+            };
+
+            if m.method == IS_PRIME {
+                info!("Method isPrime and possible prime number");
+                Ok(serde_json::to_string(&Response {
+                    method: IS_PRIME.to_owned(),
+                    prime: is_prime(possible_prime),
+                })
+                .unwrap())
+            } else {
+                error!("Method is not isPrime");
+                Err(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "Method is not isPrime",
+                ))
+            }
+        }
+        Err(_) => {
+            error!("Message is not a valid JSON or Request type");
+            Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Message is not a Request",
+            ))
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use problem_01::{server, DEFAULT_PORT, MAX_CONNECTIONS};
+use protohackers_core::test_support::{LineClient, TestServer};
+use protohackers_core::ServerConfig;
+
+async fn start() -> TestServer {
+    TestServer::start(|listener, shutdown| {
+        server::run(
+            listener,
+            ServerConfig::new(DEFAULT_PORT, MAX_CONNECTIONS),
+            shutdown,
+        )
+    })
+    .await
+}
+
+#[tokio::test]
+async fn answers_is_prime_requests() {
+    let server = start().await;
+    let mut client = LineClient::connect(server.address()).await;
+
+    client.send(r#"{"method":"isPrime","number":7}"#).await;
+    client.expect(r#"{"method":"isPrime","prime":true}"#).await;
+
+    client.send(r#"{"method":"isPrime","number":8}"#).await;
+    client.expect(r#"{"method":"isPrime","prime":false}"#).await;
+
+    client
+        .send(r#"{"number":2,"method":"isPrime","extra":null}"#)
+        .await;
+    client.expect(r#"{"method":"isPrime","prime":true}"#).await;
+
+    client.send(r#"{"method":"isPrime","number":-3}"#).await;
+    client.expect(r#"{"method":"isPrime","prime":false}"#).await;
+
+    client.send(r#"{"method":"isPrime","number":7.5}"#).await;
+    client.expect(r#"{"method":"isPrime","prime":false}"#).await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn answers_malformed_requests_with_a_malformed_response() {
+    let server = start().await;
+    let mut client = LineClient::connect(server.address()).await;
+
+    client.send(r#"{"method":"isComposite","number":7}"#).await;
+    client.expect("}mal").await;
+
+    client.send(r#"{"method":"isPrime","number":"7"}"#).await;
+    client.expect("}mal").await;
+
+    client.send("not json").await;
+    client.expect("}mal").await;
+
+    server.stop().await.unwrap();
+}
@@ -1,4 +1,5 @@
 use crate::frame::{self, Frame};
+use crate::metrics::Metrics;
 
 use bytes::{Buf, BytesMut};
 use std::io::Cursor;
@@ -10,13 +11,15 @@ use tracing::{debug, info};
 pub struct Connection {
     stream: BufWriter<TcpStream>,
     buffer: BytesMut,
+    metrics: Metrics,
 }
 
 impl Connection {
-    pub fn new(socket: TcpStream) -> Connection {
+    pub(crate) fn new(socket: TcpStream, metrics: Metrics) -> Connection {
         Connection {
             stream: BufWriter::new(socket),
             buffer: BytesMut::with_capacity(4 * 1024),
+            metrics,
         }
     }
 
@@ -28,7 +31,10 @@ impl Connection {
                 return Ok(Some(frame));
             }
 
-            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            let n = self.stream.read_buf(&mut self.buffer).await?;
+            self.metrics.bytes_received.inc_by(n as u64);
+
+            if 0 == n {
                 if self.buffer.is_empty() {
                     return Ok(None);
                 } else {
@@ -57,14 +63,18 @@ impl Connection {
                 Ok(Some(frame))
             }
             Err(Incomplete) => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => {
+                self.metrics.parse_errors.inc();
+                Err(e.into())
+            }
         }
     }
 
     pub async fn write_frame(&mut self, frame: &Frame) -> tokio::io::Result<()> {
         debug!(?frame);
         if let Frame::Response(mean) = frame {
-            let _ = self.stream.write_i32(*mean as i32).await?;
+            self.stream.write_i32(*mean as i32).await?;
+            self.metrics.bytes_sent.inc_by(4);
             info!("Write frame Response to stream");
             return self.stream.flush().await;
         }
@@ -4,11 +4,11 @@ pub use connection::Connection;
 pub mod frame;
 pub use frame::Frame;
 
-pub mod server;
+mod metrics;
 
-mod shutdown;
-use shutdown::Shutdown;
+pub mod server;
 
+pub const MAX_CONNECTIONS: usize = 5;
 pub const DEFAULT_PORT: u16 = 1222;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
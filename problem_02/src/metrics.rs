@@ -0,0 +1,24 @@
+use protohackers_core::metrics::{Counter, CounterVec, Registry};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Metrics {
+    pub(crate) frames_received: CounterVec,
+    pub(crate) parse_errors: Counter,
+    pub(crate) bytes_received: Counter,
+    pub(crate) bytes_sent: Counter,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Metrics {
+        Metrics {
+            frames_received: registry.counter_vec(
+                "frames_received_total",
+                "Frames parsed, by frame type.",
+                "type",
+            ),
+            parse_errors: registry.counter("parse_errors_total", "Frames that failed to parse."),
+            bytes_received: registry.counter("bytes_received_total", "Bytes read from clients."),
+            bytes_sent: registry.counter("bytes_sent_total", "Bytes written to clients."),
+        }
+    }
+}
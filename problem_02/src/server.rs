@@ -1,120 +1,61 @@
-use crate::{frame::Frame, Connection, Shutdown};
+use crate::{frame::Frame, metrics::Metrics, Connection};
 
+use protohackers_core::{metrics::Registry, ConnectionHandler, Server, ServerConfig, Shutdown};
 use std::collections::BTreeMap;
 use std::future::Future;
-use std::sync::Arc;
+use std::net::SocketAddr;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc, Semaphore};
-use tokio::time::{self, Duration};
-use tracing::{debug, error, info};
+use tracing::{debug, info};
 
-struct Listener {
-    listener: TcpListener,
-    limit_connections: Arc<Semaphore>,
-    notify_shutdown: broadcast::Sender<()>,
-    shutdown_complete_rx: mpsc::Receiver<()>,
-    shutdown_complete_tx: mpsc::Sender<()>,
+/// Creates a [`Handler`] for every accepted connection.
+struct MeansToAnEnd {
+    metrics: Metrics,
 }
 
 struct Handler {
     connection: Connection,
     shutdown: Shutdown,
     local_db: BTreeMap<Timestamp, Price>,
-    _shutdown_complete: mpsc::Sender<()>,
+    metrics: Metrics,
 }
 
 type Timestamp = i32;
 type Price = i32;
 
-const MAX_CONNECTIONS: usize = 5;
-
-pub async fn run(listener: TcpListener, shutdown: impl Future) -> crate::Result<()> {
-    let (notify_shutdown, _) = broadcast::channel(1);
-    let (shutdown_complete_tx, shutdown_complete_rx) = mpsc::channel(1);
-
-    let mut server = Listener {
-        listener,
-        limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
-        notify_shutdown,
-        shutdown_complete_tx,
-        shutdown_complete_rx,
+pub async fn run(
+    listener: TcpListener,
+    config: ServerConfig,
+    shutdown: impl Future,
+) -> crate::Result<()> {
+    let registry = Registry::new("means_to_an_end");
+    let means_to_an_end = MeansToAnEnd {
+        metrics: Metrics::new(&registry),
     };
 
-    tokio::select! {
-        res = server.run() => {
-            if let Err(err) = res {
-                error!(cause = %err, "failed to accept");
-            }
-        }
-        _ = shutdown => {
-            info!("shutting down");
-        }
-    }
-
-    let Listener {
-        mut shutdown_complete_rx,
-        shutdown_complete_tx,
-        notify_shutdown,
-        ..
-    } = server;
-
-    drop(notify_shutdown);
-    drop(shutdown_complete_tx);
-
-    let _ = shutdown_complete_rx.recv().await;
-
-    Ok(())
+    Server::new(listener, means_to_an_end)
+        .max_connections(config.max_connections)
+        .metrics(registry, config.bind_metrics().await?)
+        .run(shutdown)
+        .await
 }
 
-impl Listener {
-    async fn run(&mut self) -> crate::Result<()> {
-        info!("accepting inbound connections");
-
-        loop {
-            let permit = self
-                .limit_connections
-                .clone()
-                .acquire_owned()
-                .await
-                .unwrap();
-
-            let socket = self.accept().await?;
-
-            let mut handler = Handler {
-                connection: Connection::new(socket),
-                shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
-                local_db: BTreeMap::new(),
-                _shutdown_complete: self.shutdown_complete_tx.clone(),
-            };
-
-            info!("Created new handler");
-
-            tokio::spawn(async move {
-                if let Err(err) = handler.run().await {
-                    error!(cause = ?err, "connection error");
-                }
-                drop(permit);
-            });
-        }
-    }
-
-    async fn accept(&mut self) -> crate::Result<TcpStream> {
-        let mut backoff = 1;
-
-        loop {
-            match self.listener.accept().await {
-                Ok((socket, _)) => return Ok(socket),
-                Err(err) => {
-                    if backoff > 64 {
-                        return Err(err.into());
-                    }
-                }
-            }
-
-            time::sleep(Duration::from_secs(backoff)).await;
-
-            backoff *= 2;
-        }
+impl ConnectionHandler for MeansToAnEnd {
+    async fn handle(
+        &self,
+        socket: TcpStream,
+        _address: SocketAddr,
+        shutdown: Shutdown,
+    ) -> crate::Result<()> {
+        let mut handler = Handler {
+            connection: Connection::new(socket, self.metrics.clone()),
+            shutdown,
+            local_db: BTreeMap::new(),
+            metrics: self.metrics.clone(),
+        };
+
+        info!("Created new handler");
+
+        handler.run().await
     }
 }
 
@@ -138,9 +79,11 @@ impl Handler {
 
             match frame {
                 Frame::Insert { timestamp, price } => {
+                    self.metrics.frames_received.with("insert").inc();
                     self.local_db.insert(timestamp, price);
                 }
                 Frame::Query { mintime, maxtime } => {
+                    self.metrics.frames_received.with("query").inc();
                     debug!(?mintime, ?maxtime);
 
                     if mintime <= maxtime {
@@ -0,0 +1,84 @@
+use problem_02::{server, DEFAULT_PORT, MAX_CONNECTIONS};
+use protohackers_core::test_support::{BinaryClient, TestServer};
+use protohackers_core::ServerConfig;
+
+async fn start() -> TestServer {
+    TestServer::start(|listener, shutdown| {
+        server::run(
+            listener,
+            ServerConfig::new(DEFAULT_PORT, MAX_CONNECTIONS),
+            shutdown,
+        )
+    })
+    .await
+}
+
+fn insert(timestamp: i32, price: i32) -> Vec<u8> {
+    [&b"I"[..], &timestamp.to_be_bytes(), &price.to_be_bytes()].concat()
+}
+
+fn query(mintime: i32, maxtime: i32) -> Vec<u8> {
+    [&b"Q"[..], &mintime.to_be_bytes(), &maxtime.to_be_bytes()].concat()
+}
+
+#[tokio::test]
+async fn answers_the_mean_of_the_example_session() {
+    let server = start().await;
+    let mut client = BinaryClient::connect(server.address()).await;
+
+    client.send(&insert(12345, 101)).await;
+    client.send(&insert(12346, 102)).await;
+    client.send(&insert(12347, 100)).await;
+    client.send(&insert(40960, 5)).await;
+    client.send(&query(12288, 16384)).await;
+    client.expect(&101i32.to_be_bytes()).await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn answers_zero_for_empty_and_inverted_ranges() {
+    let server = start().await;
+    let mut client = BinaryClient::connect(server.address()).await;
+
+    client.send(&query(0, 100)).await;
+    client.expect(&0i32.to_be_bytes()).await;
+
+    client.send(&insert(50, 10)).await;
+    client.send(&query(100, 0)).await;
+    client.expect(&0i32.to_be_bytes()).await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn keeps_prices_per_session() {
+    let server = start().await;
+    let mut first = BinaryClient::connect(server.address()).await;
+    let mut second = BinaryClient::connect(server.address()).await;
+
+    first.send(&insert(1, 10)).await;
+    first.send(&insert(2, 20)).await;
+    second.send(&insert(1, -30)).await;
+
+    first.send(&query(0, 10)).await;
+    first.expect(&15i32.to_be_bytes()).await;
+    second.send(&query(0, 10)).await;
+    second.expect(&(-30i32).to_be_bytes()).await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn accepts_messages_split_across_writes() {
+    let server = start().await;
+    let mut client = BinaryClient::connect(server.address()).await;
+
+    let message = insert(7, 42);
+    client.send(&message[..3]).await;
+    client.send(&message[3..]).await;
+    client.send(&query(7, 7)).await;
+    client.expect(&42i32.to_be_bytes()).await;
+
+    server.stop().await.unwrap();
+}
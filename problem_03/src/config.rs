@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use protohackers_core::ServerConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{DEFAULT_PORT, MAX_CONNECTIONS};
+
+/// The spec requires names of at least 16 characters to be accepted.
+pub const MIN_NAME_LENGTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub server: ServerConfig,
+    /// Longest name a user or room may have, at least [`MIN_NAME_LENGTH`].
+    pub max_name_length: usize,
+    /// ASCII characters allowed in names besides letters and digits, e.g.
+    /// `"-_"`.
+    pub name_chars: String,
+    /// Messages kept per room, the last ones said.
+    pub history_size: usize,
+    /// Rooms whose history is kept, the history of the room talked in least
+    /// recently is dropped first.
+    pub history_rooms: usize,
+    /// Whether users that join a room get its history after the list of
+    /// members.
+    pub replay_history: bool,
+    /// JSON lines file the history is kept in across restarts.
+    pub history_file: Option<PathBuf>,
+    /// Messages a room buffers for its slowest member.
+    pub room_capacity: usize,
+    /// What happens to a user that falls more than `room_capacity` messages
+    /// behind.
+    pub on_lag: OnLag,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnLag {
+    /// Tell the user how many messages it missed and carry on.
+    #[default]
+    Notify,
+    /// Tell the user how many messages it missed and disconnect it.
+    Disconnect,
+}
+
+impl Config {
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        if self.max_name_length < MIN_NAME_LENGTH {
+            return Err(format!("max_name_length must be at least {MIN_NAME_LENGTH}").into());
+        }
+        if self.room_capacity == 0 {
+            return Err("room_capacity must be at least 1".into());
+        }
+        if !self.name_chars.chars().all(|c| c.is_ascii_graphic()) {
+            return Err("name_chars must be printable ASCII characters".into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            server: ServerConfig::new(DEFAULT_PORT, MAX_CONNECTIONS),
+            max_name_length: MIN_NAME_LENGTH,
+            name_chars: String::new(),
+            history_size: 20,
+            history_rooms: 100,
+            replay_history: false,
+            history_file: None,
+            room_capacity: 100,
+            on_lag: OnLag::default(),
+        }
+    }
+}
+
+impl AsMut<ServerConfig> for Config {
+    fn as_mut(&mut self) -> &mut ServerConfig {
+        &mut self.server
+    }
+}
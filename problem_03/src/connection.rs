@@ -1,18 +1,18 @@
-use crate::{Message, Result, Username};
+use crate::{metrics::Metrics, ConnectionId, Message, Result};
 use futures::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
 use tokio::sync::broadcast::Sender;
 use tokio_util::codec::{Framed, LinesCodec};
 use tracing::{debug, error, info};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct BroadcastMessage {
-    pub(crate) from: Username,
+    pub(crate) from: ConnectionId,
     pub(crate) message: Message,
 }
 
 impl BroadcastMessage {
-    pub fn new(from: Username, message: Message) -> Self {
+    pub fn new(from: ConnectionId, message: Message) -> Self {
         BroadcastMessage { from, message }
     }
 }
@@ -20,24 +20,24 @@ impl BroadcastMessage {
 #[derive(Debug)]
 pub struct Connection {
     pub stream: Framed<TcpStream, LinesCodec>,
-    pub broadcast: Sender<BroadcastMessage>,
+    metrics: Metrics,
 }
 
 impl Connection {
-    pub fn new(socket: TcpStream, sender: Sender<BroadcastMessage>) -> Connection {
+    pub(crate) fn new(socket: TcpStream, metrics: Metrics) -> Connection {
         Connection {
             stream: Framed::new(socket, LinesCodec::new()),
-            broadcast: sender,
+            metrics,
         }
     }
 
     pub async fn red_next_frame(&mut self) -> Result<Option<String>> {
-        return if let Some(Ok(frame)) = self.stream.next().await {
+        if let Some(Ok(frame)) = self.stream.next().await {
             info!("Frame for parsing the username parsed");
             Ok(Some(frame))
         } else {
             Err("connection reset by peer".into())
-        };
+        }
     }
 
     pub async fn write_frame(&mut self, response: String) -> Result<()> {
@@ -46,15 +46,22 @@ impl Connection {
             error!("Could not write frame to stream");
             return Err(e.to_string().into());
         }
+        self.metrics.bytes_sent.inc_by(response.len() as u64 + 1);
         info!("Wrote to frame: {}", response);
         Ok(())
     }
 
-    pub fn broadcast_message(&mut self, message: BroadcastMessage) -> Result<()> {
-        match self.broadcast.send(message.clone()) {
+    /// Sends `message` to everyone in the room of `broadcast`.
+    pub fn broadcast_message(
+        &mut self,
+        broadcast: &Sender<BroadcastMessage>,
+        message: BroadcastMessage,
+    ) -> Result<()> {
+        match broadcast.send(message.clone()) {
             Ok(n) => info!("Sent broadcast: {n}"),
             Err(e) => error!("Could not send broadcast: {e}"),
         }
+        self.metrics.broadcast_backlog.set(broadcast.len() as i64);
         Ok(())
     }
 }
@@ -1,47 +1,257 @@
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{broadcast, mpsc, RwLock};
 
 use tracing::info;
 
-use crate::Result;
+use crate::history::History;
+use crate::{BroadcastMessage, Config, ConnectionId, Message, RoomName, Username};
+
+/// Why a name was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum NameError {
+    Invalid(String),
+    Taken(String),
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameError::Invalid(name) => write!(f, "Invalid name: {name}"),
+            NameError::Taken(name) => write!(f, "The name {name} is taken"),
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
+
+/// What a user or room name may look like.
+#[derive(Debug, Clone)]
+pub(crate) struct NameRules {
+    max_length: usize,
+    extra_chars: String,
+}
+
+impl NameRules {
+    pub(crate) fn new(config: &Config) -> Self {
+        NameRules {
+            max_length: config.max_name_length,
+            extra_chars: config.name_chars.clone(),
+        }
+    }
+
+    /// Names are 1 to `max_length` ASCII letters, digits and the configured
+    /// extra characters.
+    pub(crate) fn is_valid(&self, name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= self.max_length
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || self.extra_chars.contains(c))
+    }
+
+    /// Describes the valid names for a client that sent an invalid one.
+    pub(crate) fn describe(&self) -> String {
+        if self.extra_chars.is_empty() {
+            format!("1 to {} ASCII letters or digits", self.max_length)
+        } else {
+            format!(
+                "1 to {} ASCII letters, digits or any of {}",
+                self.max_length, self.extra_chars
+            )
+        }
+    }
+}
+
+#[derive(Debug)]
+struct User {
+    name: Username,
+    /// Lines sent to this user alone, such as private messages.
+    outbound: mpsc::Sender<Message>,
+}
+
+#[derive(Debug, Default)]
+struct Users {
+    names: HashMap<ConnectionId, User>,
+    /// The connection of each name, lowercased as names are unique ignoring
+    /// case.
+    connections: HashMap<String, ConnectionId>,
+}
+
+#[derive(Debug)]
+struct Room {
+    members: Vec<(ConnectionId, Username)>,
+    broadcast: broadcast::Sender<BroadcastMessage>,
+}
+
+/// A user's place in a room, it receives the messages of the room.
+#[derive(Debug)]
+pub(crate) struct Membership {
+    pub(crate) room: RoomName,
+    pub(crate) broadcast: broadcast::Sender<BroadcastMessage>,
+    pub(crate) receiver: broadcast::Receiver<BroadcastMessage>,
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct Db {
-    users: Arc<RwLock<Vec<String>>>,
+    rules: NameRules,
+    /// Messages a room buffers for its slowest member.
+    room_capacity: usize,
+    users: Arc<RwLock<Users>>,
+    rooms: Arc<RwLock<HashMap<RoomName, Room>>>,
+    history: Arc<Mutex<History>>,
 }
 
 impl Db {
-    pub fn new() -> Self {
+    pub fn new(rules: NameRules, history: History, room_capacity: usize) -> Self {
         Db {
-            users: Arc::new(RwLock::new(Vec::default())),
+            rules,
+            room_capacity,
+            users: Arc::new(RwLock::new(Users::default())),
+            rooms: Arc::new(RwLock::new(HashMap::default())),
+            history: Arc::new(Mutex::new(history)),
         }
     }
 
-    pub async fn insert_user(&self, username: String) -> Result<()> {
-        if !username.is_empty() && username.chars().all(char::is_alphanumeric) {
-            self.users.write().await.push(username);
-            Ok(())
-        } else {
-            Err(format!("Cannot insert new user: {username}").into())
+    /// A panic while holding the lock leaves the history consistent, so a
+    /// poisoned lock is used anyway.
+    fn history(&self) -> MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn rules(&self) -> &NameRules {
+        &self.rules
+    }
+
+    /// Registers `username` and its `outbound` channel for the connection
+    /// `id`, unless the name is invalid or another connection has it in any
+    /// case.
+    pub async fn insert_user(
+        &self,
+        id: ConnectionId,
+        username: String,
+        outbound: mpsc::Sender<Message>,
+    ) -> std::result::Result<(), NameError> {
+        if !self.rules.is_valid(&username) {
+            return Err(NameError::Invalid(username));
+        }
+
+        let mut users = self.users.write().await;
+        let key = username.to_ascii_lowercase();
+        if users.connections.contains_key(&key) {
+            return Err(NameError::Taken(username));
+        }
+        users.connections.insert(key, id);
+        users.names.insert(
+            id,
+            User {
+                name: username,
+                outbound,
+            },
+        );
+        Ok(())
+    }
+
+    /// The name and outbound channel of the user called `name` in any case.
+    pub async fn user(&self, name: &str) -> Option<(Username, mpsc::Sender<Message>)> {
+        let users = self.users.read().await;
+        let id = users.connections.get(&name.to_ascii_lowercase())?;
+        users
+            .names
+            .get(id)
+            .map(|user| (user.name.clone(), user.outbound.clone()))
+    }
+
+    /// Adds the user of `id` to `room`, which is created if nobody is in it
+    /// yet, and returns the membership with the names of the members that
+    /// were already there and the history of the room.
+    pub async fn join(
+        &self,
+        room: RoomName,
+        id: ConnectionId,
+        username: String,
+    ) -> (Membership, Vec<Username>, Vec<Message>) {
+        let mut rooms = self.rooms.write().await;
+        let entry = rooms.entry(room.clone()).or_insert_with(|| {
+            info!("Open room {room}");
+            Room {
+                members: Vec::new(),
+                broadcast: broadcast::channel(self.room_capacity).0,
+            }
+        });
+
+        let members = entry.members.iter().map(|(_, name)| name.clone()).collect();
+        entry.members.push((id, username));
+
+        let history = self.history().messages(&room);
+        let membership = Membership {
+            room,
+            broadcast: entry.broadcast.clone(),
+            receiver: entry.broadcast.subscribe(),
+        };
+        (membership, members, history)
+    }
+
+    /// Keeps `message` in the history of `room` and broadcasts it with
+    /// `send`. Nobody joins the room meanwhile, so a newcomer gets the
+    /// message either live or replayed, never both.
+    pub async fn say<T>(&self, room: &str, message: &str, send: impl FnOnce() -> T) -> T {
+        let _rooms = self.rooms.read().await;
+        self.history().push(room, message);
+        send()
+    }
+
+    /// Waits until the history said so far is written to its file, if there
+    /// is one.
+    pub async fn flush_history(&self) {
+        let flushed = self.history().flush();
+        if let Some(flushed) = flushed {
+            let _ = flushed.await;
+        }
+    }
+
+    /// Removes the user of `id` from `room`, a room without members is
+    /// closed.
+    pub async fn leave(&self, room: &str, id: ConnectionId) {
+        let mut rooms = self.rooms.write().await;
+        if let Some(entry) = rooms.get_mut(room) {
+            entry.members.retain(|(member, _)| *member != id);
+            if entry.members.is_empty() {
+                info!("Close room {room}");
+                rooms.remove(room);
+            }
         }
     }
 
-    pub async fn get_room_members(&self, username: String) -> Vec<String> {
-        info!("Get room members: {:?}", self.users);
-        self.users
+    /// The names of the members of `room`, in the order they joined.
+    pub async fn members(&self, room: &str) -> Vec<Username> {
+        self.rooms
             .read()
             .await
-            .clone()
-            .into_iter()
-            .filter(|n| {
-                info!("{n} is in the room");
-                *n != username
-            })
-            .collect()
+            .get(room)
+            .map(|room| room.members.iter().map(|(_, name)| name.clone()).collect())
+            .unwrap_or_default()
     }
 
-    pub async fn remove(&self, username: String) -> Result<()> {
-        self.users.write().await.retain(|n| *n != username);
-        Ok(())
+    /// The open rooms with their number of members, by name.
+    pub async fn rooms(&self) -> Vec<(RoomName, usize)> {
+        let mut rooms: Vec<_> = self
+            .rooms
+            .read()
+            .await
+            .iter()
+            .map(|(name, room)| (name.clone(), room.members.len()))
+            .collect();
+        rooms.sort();
+        rooms
+    }
+
+    /// Frees the name of the connection `id`.
+    pub async fn remove(&self, id: ConnectionId) {
+        let mut users = self.users.write().await;
+        if let Some(user) = users.names.remove(&id) {
+            users.connections.remove(&user.name.to_ascii_lowercase());
+        }
     }
 }
@@ -0,0 +1,299 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write as _};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task;
+use tracing::{error, warn};
+
+use crate::{Message, RoomName};
+
+/// A message said in a room, stored as one JSON object per line.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+struct Entry {
+    room: RoomName,
+    message: Message,
+}
+
+#[derive(Debug, Default)]
+struct Room {
+    messages: VecDeque<Message>,
+    /// When the last message was said, counted in messages.
+    said_at: u64,
+}
+
+/// What the writer task does with the history file, in the order sent.
+#[derive(Debug)]
+enum Write {
+    /// Appends the line of one message.
+    Append(Vec<u8>),
+    /// Replaces the file with the lines of the kept messages.
+    Rewrite(Vec<u8>),
+    /// Answered once every write sent before it is done.
+    Flush(oneshot::Sender<()>),
+}
+
+/// The most writes done with one blocking call.
+const BATCH: usize = 256;
+
+/// The file the history is appended to, owned by the writer task.
+#[derive(Debug)]
+struct HistoryFile {
+    path: PathBuf,
+    file: File,
+}
+
+/// The writer task of the history file and the lines the file holds.
+#[derive(Debug)]
+struct Writer {
+    writes: mpsc::UnboundedSender<Write>,
+    lines: usize,
+}
+
+/// The last messages said in each room, kept after everyone left the room.
+/// Only the `max_rooms` rooms talked in most recently are kept.
+#[derive(Debug)]
+pub(crate) struct History {
+    size: usize,
+    max_rooms: usize,
+    rooms: HashMap<RoomName, Room>,
+    said: u64,
+    writer: Option<Writer>,
+}
+
+impl History {
+    /// Keeps the last `size` messages of each of `rooms` rooms in memory
+    /// only.
+    pub(crate) fn new(size: usize, rooms: usize) -> Self {
+        History {
+            size,
+            max_rooms: rooms,
+            rooms: HashMap::new(),
+            said: 0,
+            writer: None,
+        }
+    }
+
+    /// Keeps the last `size` messages of each of `rooms` rooms in the file
+    /// at `path` as well, and loads the messages it already holds. The file
+    /// is written by a task spawned on the current runtime.
+    ///
+    /// A message whose line cannot be read back, such as one half written
+    /// when the server crashed, is lost. The file is rewritten with only the
+    /// kept messages once it holds more, and again whenever it grew to twice
+    /// what can be kept.
+    pub(crate) fn open(path: &Path, size: usize, rooms: usize) -> io::Result<Self> {
+        let mut history = History::new(size, rooms);
+
+        let mut content = Vec::new();
+        match File::open(path) {
+            Ok(mut file) => {
+                file.read_to_end(&mut content)?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let mut lines = 0;
+        for (number, line) in content.split(|b| *b == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            lines += 1;
+            match serde_json::from_slice::<Entry>(line) {
+                Ok(entry) => {
+                    history.keep(entry.room, entry.message);
+                }
+                Err(e) => warn!("Skipping line {} of history {path:?}: {e}", number + 1),
+            }
+        }
+
+        if lines > history.kept() || content.last().is_some_and(|b| *b != b'\n') {
+            replace(path, &history.content()?)?;
+            lines = history.kept();
+        }
+
+        let file = HistoryFile {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        };
+        let (writes, receive_writes) = mpsc::unbounded_channel();
+        tokio::spawn(write(file, receive_writes));
+        history.writer = Some(Writer { writes, lines });
+        Ok(history)
+    }
+
+    fn line(room: &str, message: &str) -> io::Result<Vec<u8>> {
+        let mut line = serde_json::to_vec(&Entry {
+            room: room.to_string(),
+            message: message.to_string(),
+        })?;
+        line.push(b'\n');
+        Ok(line)
+    }
+
+    fn kept(&self) -> usize {
+        self.rooms.values().map(|room| room.messages.len()).sum()
+    }
+
+    /// The lines of the kept messages, the rooms talked in least recently
+    /// first so they are dropped first after a restart too.
+    fn content(&self) -> io::Result<Vec<u8>> {
+        let mut rooms: Vec<_> = self.rooms.iter().collect();
+        rooms.sort_by_key(|(_, room)| room.said_at);
+
+        let mut content = Vec::new();
+        for (name, room) in rooms {
+            for message in &room.messages {
+                content.extend(Self::line(name, message)?);
+            }
+        }
+        Ok(content)
+    }
+
+    /// Returns whether `message` is kept, nothing is without a size or
+    /// rooms.
+    fn keep(&mut self, name: RoomName, message: Message) -> bool {
+        if self.size == 0 || self.max_rooms == 0 {
+            return false;
+        }
+
+        if !self.rooms.contains_key(&name) && self.rooms.len() == self.max_rooms {
+            let oldest = self
+                .rooms
+                .iter()
+                .min_by_key(|(_, room)| room.said_at)
+                .map(|(name, _)| name.clone());
+            if let Some(oldest) = oldest {
+                self.rooms.remove(&oldest);
+            }
+        }
+
+        self.said += 1;
+        let room = self.rooms.entry(name).or_default();
+        room.said_at = self.said;
+        if room.messages.len() == self.size {
+            room.messages.pop_front();
+        }
+        room.messages.push_back(message);
+        true
+    }
+
+    /// Adds `message` to the history of `room`, dropping the oldest one once
+    /// the room has `size` messages, and the history of the room talked in
+    /// least recently once there are `max_rooms` rooms. The file is written
+    /// later by the writer task.
+    pub(crate) fn push(&mut self, room: &str, message: &str) {
+        if !self.keep(room.to_string(), message.to_string()) {
+            return;
+        }
+        let Some(lines) = self.writer.as_ref().map(|writer| writer.lines + 1) else {
+            return;
+        };
+
+        let (write, lines) = if lines >= self.size.saturating_mul(self.max_rooms).saturating_mul(2)
+        {
+            (self.content().map(Write::Rewrite), self.kept())
+        } else {
+            (Self::line(room, message).map(Write::Append), lines)
+        };
+        // A lost write only loses the message after a restart.
+        match write {
+            Ok(write) => self.send(write),
+            Err(e) => error!("Could not write the history of {room}: {e}"),
+        }
+        if let Some(writer) = &mut self.writer {
+            writer.lines = lines;
+        }
+    }
+
+    fn send(&self, write: Write) {
+        if let Some(writer) = &self.writer {
+            if writer.writes.send(write).is_err() {
+                error!("The history writer stopped, the message is not kept after a restart");
+            }
+        }
+    }
+
+    /// Resolves once every message pushed so far is written to the file.
+    pub(crate) fn flush(&self) -> Option<oneshot::Receiver<()>> {
+        self.writer.as_ref()?;
+        let (done, flushed) = oneshot::channel();
+        self.send(Write::Flush(done));
+        Some(flushed)
+    }
+
+    /// The kept messages of `room`, oldest first.
+    pub(crate) fn messages(&self, room: &str) -> Vec<Message> {
+        self.rooms
+            .get(room)
+            .map(|room| room.messages.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl HistoryFile {
+    fn write(&mut self, write: Write) {
+        match write {
+            Write::Append(line) => {
+                if let Err(e) = self.file.write_all(&line) {
+                    error!("Could not write the history {:?}: {e}", self.path);
+                }
+            }
+            Write::Rewrite(content) => {
+                if let Err(e) = replace(&self.path, &content).and_then(|()| self.reopen()) {
+                    error!("Could not rewrite the history {:?}: {e}", self.path);
+                }
+            }
+            Write::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Does the `writes` to `file` on the blocking pool until every sender is
+/// dropped, so nobody waits on the disk to say something.
+async fn write(mut file: HistoryFile, mut writes: mpsc::UnboundedReceiver<Write>) {
+    while let Some(first) = writes.recv().await {
+        let mut batch = vec![first];
+        while batch.len() < BATCH {
+            match writes.try_recv() {
+                Ok(write) => batch.push(write),
+                Err(_) => break,
+            }
+        }
+
+        file = match task::spawn_blocking(move || {
+            for write in batch {
+                file.write(write);
+            }
+            file
+        })
+        .await
+        {
+            Ok(file) => file,
+            Err(e) => {
+                error!("The history writer stopped: {e}");
+                return;
+            }
+        };
+    }
+}
+
+/// Replaces the file at `path` with `content`, through a temporary file so a
+/// crash leaves either the old or the new file.
+fn replace(path: &Path, content: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
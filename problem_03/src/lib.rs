@@ -1,20 +1,27 @@
+mod config;
 mod connection;
 
+pub use config::{Config, OnLag, MIN_NAME_LENGTH};
+
 pub use connection::{BroadcastMessage, Connection};
 use tokio::net::unix::SocketAddr;
 
 pub mod server;
 
 mod db;
-mod shutdown;
-
-use shutdown::Shutdown;
+mod history;
+mod metrics;
 
+pub const MAX_CONNECTIONS: usize = 100;
 pub const DEFAULT_PORT: u16 = 1222;
-pub const DEFAULT_IP: &str = "0.0.0.0";
+/// The room users are in after they named themselves.
+pub const DEFAULT_ROOM: &str = "lobby";
 
 pub type Username = String;
 pub type Message = String;
+pub type RoomName = String;
+/// Identifies a client by the address it connected from.
+pub type ConnectionId = std::net::SocketAddr;
 pub type Address = SocketAddr;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
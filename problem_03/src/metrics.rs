@@ -0,0 +1,35 @@
+use protohackers_core::metrics::{Counter, CounterVec, Gauge, Registry};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Metrics {
+    pub(crate) lines_received: CounterVec,
+    pub(crate) parse_errors: Counter,
+    pub(crate) broadcast_backlog: Gauge,
+    pub(crate) bytes_received: Counter,
+    pub(crate) bytes_sent: Counter,
+    pub(crate) messages_dropped: CounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Metrics {
+        Metrics {
+            lines_received: registry.counter_vec(
+                "lines_received_total",
+                "Lines received, by kind of line.",
+                "type",
+            ),
+            parse_errors: registry.counter("parse_errors_total", "Lines that failed to decode."),
+            broadcast_backlog: registry.gauge(
+                "broadcast_backlog",
+                "Messages the slowest client has not received yet.",
+            ),
+            bytes_received: registry.counter("bytes_received_total", "Bytes read from clients."),
+            bytes_sent: registry.counter("bytes_sent_total", "Bytes written to clients."),
+            messages_dropped: registry.counter_vec(
+                "messages_dropped_total",
+                "Messages a user never got, by reason.",
+                "reason",
+            ),
+        }
+    }
+}
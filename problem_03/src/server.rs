@@ -1,123 +1,105 @@
-use crate::{BroadcastMessage, Connection, Shutdown};
+use crate::{BroadcastMessage, Config, Connection, ConnectionId, OnLag, RoomName, DEFAULT_ROOM};
 
-use crate::db::Db;
+use crate::db::{Db, Membership, NameError, NameRules};
+use crate::history::History;
+use crate::metrics::Metrics;
 use futures::StreamExt;
+use protohackers_core::{metrics::Registry, ConnectionHandler, Server, Shutdown};
 use std::future::Future;
-use std::sync::Arc;
+use std::net::SocketAddr;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc, Semaphore};
-use tokio::time::{self, Duration};
+use tokio::sync::{
+    broadcast::{error::RecvError, Sender},
+    mpsc,
+};
 use tracing::{debug, error, info};
 
-struct Listener {
-    listener: TcpListener,
-    limit_connections: Arc<Semaphore>,
-    notify_shutdown: broadcast::Sender<()>,
-    broadcast_message: broadcast::Sender<BroadcastMessage>,
-    shutdown_complete_rx: mpsc::Receiver<()>,
-    shutdown_complete_tx: mpsc::Sender<()>,
+/// Private messages a user's outbound channel buffers.
+const OUTBOUND_CAPACITY: usize = 100;
+
+const USAGE: &str = "try /join <room>, /leave, /rooms, /who, /msg <user> <text> or /me <action>";
+
+/// State shared by all connections, creates a [`Handler`] per connection.
+struct BudgetChat {
+    db: Db,
+    metrics: Metrics,
+    replay_history: bool,
+    on_lag: OnLag,
 }
 
 struct Handler {
+    id: ConnectionId,
     connection: Connection,
     db: Db,
     shutdown: Shutdown,
-    _shutdown_complete: mpsc::Sender<()>,
+    metrics: Metrics,
+    /// The room the user is in, none after `/leave`.
+    room: Option<Membership>,
+    replay_history: bool,
+    on_lag: OnLag,
 }
 
-const MAX_CONNECTIONS: usize = 100;
-
-pub async fn run(listener: TcpListener, shutdown: impl Future) -> crate::Result<()> {
-    let (notify_shutdown, _) = broadcast::channel(1);
-    let (broadcast_message, _) = broadcast::channel(100);
-    let (shutdown_complete_tx, shutdown_complete_rx) = mpsc::channel(1);
+pub async fn run(
+    listener: TcpListener,
+    config: Config,
+    shutdown: impl Future,
+) -> crate::Result<()> {
+    config.validate()?;
 
-    let mut server = Listener {
-        listener,
-        limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
-        notify_shutdown,
-        broadcast_message,
-        shutdown_complete_tx,
-        shutdown_complete_rx,
+    let history = match &config.history_file {
+        Some(path) => History::open(path, config.history_size, config.history_rooms)
+            .map_err(|e| format!("cannot open history {path:?}: {e}"))?,
+        None => History::new(config.history_size, config.history_rooms),
     };
 
-    tokio::select! {
-        res = server.run() => {
-            if let Err(err) = res {
-                error!(cause = %err, "failed to accept");
-            }
-        }
-        _ = shutdown => {
-            info!("shutting down");
-        }
-    }
-
-    let Listener {
-        mut shutdown_complete_rx,
-        shutdown_complete_tx,
-        notify_shutdown,
-        ..
-    } = server;
-
-    drop(notify_shutdown);
-    drop(shutdown_complete_tx);
+    let registry = Registry::new("budget_chat");
+    let budget_chat = BudgetChat {
+        db: Db::new(NameRules::new(&config), history, config.room_capacity),
+        metrics: Metrics::new(&registry),
+        replay_history: config.replay_history,
+        on_lag: config.on_lag,
+    };
 
-    let _ = shutdown_complete_rx.recv().await;
+    let db = budget_chat.db.clone();
+    let res = Server::new(listener, budget_chat)
+        .max_connections(config.server.max_connections)
+        .metrics(registry, config.server.bind_metrics().await?)
+        .run(shutdown)
+        .await;
 
-    Ok(())
+    db.flush_history().await;
+    res
 }
 
-impl Listener {
-    async fn run(&mut self) -> crate::Result<()> {
-        info!("accepting inbound connections");
-        let db = Db::new();
-        loop {
-            let permit = self
-                .limit_connections
-                .clone()
-                .acquire_owned()
-                .await
-                .unwrap();
-
-            let socket = self.accept().await?;
-            let message_sender: broadcast::Sender<BroadcastMessage> =
-                self.broadcast_message.clone();
-
-            let mut handler = Handler {
-                connection: Connection::new(socket, message_sender),
-                db: db.clone(),
-                shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
-                _shutdown_complete: self.shutdown_complete_tx.clone(),
-            };
+impl ConnectionHandler for BudgetChat {
+    async fn handle(
+        &self,
+        socket: TcpStream,
+        address: SocketAddr,
+        shutdown: Shutdown,
+    ) -> crate::Result<()> {
+        let mut handler = Handler {
+            id: address,
+            connection: Connection::new(socket, self.metrics.clone()),
+            db: self.db.clone(),
+            shutdown,
+            metrics: self.metrics.clone(),
+            room: None,
+            replay_history: self.replay_history,
+            on_lag: self.on_lag,
+        };
 
-            info!("Created new handler");
+        info!("Created new handler");
 
-            tokio::spawn(async move {
-                if let Err(err) = handler.run().await {
-                    error!(cause = ?err, "connection error");
-                }
-                drop(permit);
-            });
-        }
+        handler.run().await
     }
+}
 
-    async fn accept(&mut self) -> crate::Result<TcpStream> {
-        let mut backoff = 1;
-
-        loop {
-            match self.listener.accept().await {
-                Ok((socket, _)) => return Ok(socket),
-                Err(err) => {
-                    if backoff > 64 {
-                        return Err(err.into());
-                    }
-                }
-            }
-
-            time::sleep(Duration::from_secs(backoff)).await;
-
-            backoff *= 2;
-        }
+/// Receives the next message of the room, never without a room.
+async fn receive(room: &mut Option<Membership>) -> Result<BroadcastMessage, RecvError> {
+    match room {
+        Some(membership) => membership.receiver.recv().await,
+        None => std::future::pending().await,
     }
 }
 
@@ -125,66 +107,250 @@ impl Handler {
     async fn run(&mut self) -> crate::Result<()> {
         let welcome = String::from("Welcome to budgetchat! What shall I call you?");
         let username;
+        let (outbound, mut private_messages) = mpsc::channel(OUTBOUND_CAPACITY);
 
         // Send the Welcome message to the connected client
         let _ = self.connection.write_frame(welcome).await;
 
         // Read the answer (username) from the client
         if let Some(Ok(name)) = self.connection.stream.next().await {
+            self.metrics.lines_received.with("name").inc();
+            self.metrics.bytes_received.inc_by(name.len() as u64 + 1);
             info!("Add {name} to db");
-            self.db.insert_user(name.clone()).await?;
+            // A refused name is answered with the reason and disconnected.
+            if let Err(e) = self.db.insert_user(self.id, name.clone(), outbound).await {
+                let reply = match e {
+                    NameError::Invalid(_) => {
+                        format!("* {e}, names are {}", self.db.rules().describe())
+                    }
+                    NameError::Taken(_) => format!("* {e}"),
+                };
+                let _ = self.connection.write_frame(reply).await;
+                return Ok(());
+            }
             username = name;
         } else {
             return Ok(());
         }
 
-        // Broadcast the message "* USER has entered the room"
-        let joined_message = format!("* {username} has entered the room");
-        let _ = self
-            .connection
-            .broadcast_message(BroadcastMessage::new(username.clone(), joined_message));
-
-        // Write back directly to the client which users are currently in the room
-        let room_contains_message = format!(
-            "* The room contains {}",
-            self.db.get_room_members(username.clone()).await.join(",")
-        );
-        let _ = self.connection.write_frame(room_contains_message).await;
-
-        // Connect the client to the broadcast channel
-        let mut receiver = self.connection.broadcast.subscribe();
+        self.join(&username, DEFAULT_ROOM.to_string()).await;
 
         while !self.shutdown.is_shutdown() {
+            // Biased towards the room, so the user's own messages are taken
+            // off the room before the next line is read and a burst of lines
+            // does not make the user fall behind.
             tokio::select! {
+                biased;
+                _ = self.shutdown.recv() => {
+                    debug!("Shutdown");
+                    return Ok(());
+                }
+                message = receive(&mut self.room) => match message {
+                    Ok(message) => {
+                        info!("Message received: {message:?}");
+                        if message.from != self.id {
+                            let _ = self.connection.write_frame(message.message).await;
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        self.metrics.messages_dropped.with("lagged").inc_by(missed);
+                        match self.on_lag {
+                            OnLag::Notify => {
+                                let notice = format!("* You missed {missed} messages");
+                                let _ = self.connection.write_frame(notice).await;
+                            }
+                            OnLag::Disconnect => {
+                                info!("Disconnecting {username}, {missed} messages behind");
+                                let notice = format!("* You missed {missed} messages and are disconnected");
+                                let _ = self.connection.write_frame(notice).await;
+                                self.leave(&username).await;
+                                self.db.remove(self.id).await;
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Err(RecvError::Closed) => error!("The room of {username} was closed"),
+                },
+                Some(message) = private_messages.recv() => {
+                    let _ = self.connection.write_frame(message).await;
+                }
                 res = self.connection.stream.next() => match res {
                     Some(Ok(frame)) => {
-                        let _ = self.connection
-                            .broadcast_message(BroadcastMessage::new(username.clone(), format!("[{username}] {frame}")));
+                        self.metrics.bytes_received.inc_by(frame.len() as u64 + 1);
+                        if let Some(command) = frame.strip_prefix('/') {
+                            self.metrics.lines_received.with("command").inc();
+                            self.handle_command(&username, command).await;
+                        } else {
+                            self.metrics.lines_received.with("message").inc();
+                            self.send_message(&username, &frame).await;
+                        }
                     },
                     Some(Err(_)) => {
                         error!("Could not parse frame");
+                        self.metrics.parse_errors.inc();
                         continue;
                     },
                     None => {
-                        let message = format!("* {username} has left the room");
-                        let _ = self.connection.broadcast_message(BroadcastMessage::new(username.clone(), message.clone()));
-                        let _ = self.db.remove(username).await;
+                        self.leave(&username).await;
+                        self.db.remove(self.id).await;
                         return Ok(())
                     },
                 },
-                message = receiver.recv() => {
-                    info!("Message received: {:?}", message.as_ref().unwrap());
-                    if message.as_ref().unwrap().from != username {
-                        let _ = self.connection.write_frame(message.as_ref().unwrap().message.clone()).await;
-                    }
-                }
-                _ = self.shutdown.recv() => {
-                    debug!("Shutdown");
-                    return Ok(());
-                }
             };
         }
 
         Ok(())
     }
+
+    async fn send_message(&mut self, username: &str, message: &str) {
+        let Some(room) = &self.room else {
+            let _ = self
+                .connection
+                .write_frame("* You are not in a room, /join one".to_string())
+                .await;
+            return;
+        };
+
+        let message = format!("[{username}] {message}");
+        self.say(room.room.clone(), room.broadcast.clone(), message)
+            .await;
+    }
+
+    /// Broadcasts `message` to the room and keeps it in the room's history.
+    async fn say(&mut self, room: RoomName, broadcast: Sender<BroadcastMessage>, message: String) {
+        let broadcast_message = BroadcastMessage::new(self.id, message.clone());
+        let connection = &mut self.connection;
+        self.db
+            .say(&room, &message, || {
+                connection.broadcast_message(&broadcast, broadcast_message)
+            })
+            .await
+            .unwrap_or_else(|e| error!("Could not broadcast to {room}: {e}"));
+    }
+
+    async fn handle_command(&mut self, username: &str, command: &str) {
+        let (name, args) = match command.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim()),
+            None => (command, ""),
+        };
+
+        let reply = match name {
+            "join" if !args.is_empty() && !args.contains(char::is_whitespace) => {
+                let room = args;
+                if !self.db.rules().is_valid(room) {
+                    format!("* Invalid room name: {room}")
+                } else if self.room.as_ref().is_some_and(|m| m.room == room) {
+                    format!("* You are already in {room}")
+                } else {
+                    self.leave(username).await;
+                    self.join(username, room.to_string()).await;
+                    return;
+                }
+            }
+            "leave" if args.is_empty() => match &self.room {
+                Some(membership) => {
+                    let reply = format!("* You left {}", membership.room);
+                    self.leave(username).await;
+                    reply
+                }
+                None => "* You are not in a room".to_string(),
+            },
+            "rooms" if args.is_empty() => {
+                let rooms = self.db.rooms().await;
+                if rooms.is_empty() {
+                    "* There are no rooms".to_string()
+                } else {
+                    let rooms: Vec<_> = rooms
+                        .iter()
+                        .map(|(name, members)| format!("{name} ({members})"))
+                        .collect();
+                    format!("* Rooms: {}", rooms.join(", "))
+                }
+            }
+            "who" if args.is_empty() => match &self.room {
+                Some(membership) => format!(
+                    "* In {}: {}",
+                    membership.room,
+                    self.db.members(&membership.room).await.join(", ")
+                ),
+                None => "* You are not in a room".to_string(),
+            },
+            "me" if !args.is_empty() => match &self.room {
+                Some(membership) => {
+                    let (room, broadcast) = (membership.room.clone(), membership.broadcast.clone());
+                    self.say(room, broadcast, format!("* {username} {args}"))
+                        .await;
+                    return;
+                }
+                None => "* You are not in a room, /join one".to_string(),
+            },
+            "msg" => match args.split_once(char::is_whitespace) {
+                Some((to, text)) => match self.send_private(username, to, text.trim()).await {
+                    Some(reply) => reply,
+                    None => return,
+                },
+                None => "* Usage: /msg <user> <text>".to_string(),
+            },
+            _ => format!("* Unknown command /{command}, {USAGE}"),
+        };
+
+        let _ = self.connection.write_frame(reply).await;
+    }
+
+    /// Sends `text` to the user called `to` alone, returns the reply to the
+    /// sender if it could not be delivered.
+    async fn send_private(&mut self, username: &str, to: &str, text: &str) -> Option<String> {
+        let Some((to, outbound)) = self.db.user(to).await else {
+            return Some(format!("* No user named {to}"));
+        };
+
+        match outbound.try_send(format!("[{username} -> {to}] {text}")) {
+            Ok(()) => None,
+            Err(e) => {
+                error!("Could not send private message to {to}: {e}");
+                self.metrics.messages_dropped.with("private").inc();
+                Some(format!("* Could not deliver the message to {to}"))
+            }
+        }
+    }
+
+    /// Puts the user in `room`, tells the room about it and the user who is
+    /// there.
+    async fn join(&mut self, username: &str, room: RoomName) {
+        let (membership, members, history) =
+            self.db.join(room, self.id, username.to_string()).await;
+
+        // Broadcast the message "* USER has entered the room"
+        let joined_message = format!("* {username} has entered the room");
+        let _ = self.connection.broadcast_message(
+            &membership.broadcast,
+            BroadcastMessage::new(self.id, joined_message),
+        );
+
+        // Write back directly to the client which users are currently in the room
+        let room_contains_message = format!("* The room contains {}", members.join(","));
+        let _ = self.connection.write_frame(room_contains_message).await;
+
+        if self.replay_history {
+            for message in history {
+                let _ = self.connection.write_frame(message).await;
+            }
+        }
+
+        self.room = Some(membership);
+    }
+
+    /// Takes the user out of its room, if it is in one.
+    async fn leave(&mut self, username: &str) {
+        let Some(membership) = self.room.take() else {
+            return;
+        };
+
+        let message = format!("* {username} has left the room");
+        let _ = self.connection.broadcast_message(
+            &membership.broadcast,
+            BroadcastMessage::new(self.id, message),
+        );
+        self.db.leave(&membership.room, self.id).await;
+    }
 }
@@ -0,0 +1,434 @@
+use std::{fs, path::PathBuf};
+
+use problem_03::{server, Config, OnLag};
+use protohackers_core::test_support::{LineClient, TestServer};
+
+const WELCOME: &str = "Welcome to budgetchat! What shall I call you?";
+
+async fn start() -> TestServer {
+    start_with(Config::default()).await
+}
+
+async fn start_with(config: Config) -> TestServer {
+    TestServer::start(|listener, shutdown| server::run(listener, config, shutdown)).await
+}
+
+#[tokio::test]
+async fn chats_between_two_users() {
+    let server = start().await;
+
+    let mut bob = LineClient::connect(server.address()).await;
+    bob.expect(WELCOME).await;
+    bob.send("bob").await;
+    bob.expect("* The room contains ").await;
+
+    let mut alice = LineClient::connect(server.address()).await;
+    alice.expect(WELCOME).await;
+    alice.send("alice").await;
+    alice.expect("* The room contains bob").await;
+    bob.expect("* alice has entered the room").await;
+
+    alice.send("Hello, bob!").await;
+    bob.expect("[alice] Hello, bob!").await;
+
+    bob.send("hi alice").await;
+    alice.expect("[bob] hi alice").await;
+
+    alice.close().await;
+    bob.expect("* alice has left the room").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn disconnects_when_the_client_leaves_before_naming_itself() {
+    let server = start().await;
+
+    let mut client = LineClient::connect(server.address()).await;
+    client.expect(WELCOME).await;
+    client.close().await;
+    client.expect_closed().await;
+
+    server.stop().await.unwrap();
+}
+
+async fn join(server: &TestServer, name: &str, members: &str) -> LineClient {
+    let mut client = LineClient::connect(server.address()).await;
+    client.expect(WELCOME).await;
+    client.send(name).await;
+    client
+        .expect(&format!("* The room contains {members}"))
+        .await;
+    client
+}
+
+#[tokio::test]
+async fn scopes_messages_and_presence_to_rooms() {
+    let server = start().await;
+
+    let mut bob = join(&server, "bob", "").await;
+    let mut alice = join(&server, "alice", "bob").await;
+    bob.expect("* alice has entered the room").await;
+    let mut carol = join(&server, "carol", "bob,alice").await;
+    bob.expect("* carol has entered the room").await;
+    alice.expect("* carol has entered the room").await;
+
+    alice.send("/join rust").await;
+    bob.expect("* alice has left the room").await;
+    carol.expect("* alice has left the room").await;
+    alice.expect("* The room contains ").await;
+
+    carol.send("/join rust").await;
+    bob.expect("* carol has left the room").await;
+    alice.expect("* carol has entered the room").await;
+    carol.expect("* The room contains alice").await;
+
+    carol.send("hi alice").await;
+    alice.expect("[carol] hi alice").await;
+    bob.send("anyone?").await;
+
+    carol.send("/rooms").await;
+    carol.expect("* Rooms: lobby (1), rust (2)").await;
+
+    alice.close().await;
+    carol.expect("* alice has left the room").await;
+    bob.send("/rooms").await;
+    bob.expect("* Rooms: lobby (1), rust (1)").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn leaves_rooms_and_answers_invalid_commands() {
+    let server = start().await;
+
+    let mut bob = join(&server, "bob", "").await;
+    let mut alice = join(&server, "alice", "bob").await;
+    bob.expect("* alice has entered the room").await;
+
+    bob.send("/join lobby").await;
+    bob.expect("* You are already in lobby").await;
+    bob.send("/join no-dashes").await;
+    bob.expect("* Invalid room name: no-dashes").await;
+    bob.send("/dance").await;
+    bob.expect("* Unknown command /dance, try /join <room>, /leave, /rooms, /who, /msg <user> <text> or /me <action>")
+        .await;
+
+    bob.send("/leave").await;
+    bob.expect("* You left lobby").await;
+    alice.expect("* bob has left the room").await;
+
+    bob.send("hello?").await;
+    bob.expect("* You are not in a room, /join one").await;
+    bob.send("/leave").await;
+    bob.expect("* You are not in a room").await;
+
+    alice.send("/leave").await;
+    alice.expect("* You left lobby").await;
+    bob.send("/rooms").await;
+    bob.expect("* There are no rooms").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn refuses_names_taken_in_any_case() {
+    let server = start().await;
+
+    let mut bob = join(&server, "bob", "").await;
+
+    let mut other = LineClient::connect(server.address()).await;
+    other.expect(WELCOME).await;
+    other.send("BoB").await;
+    other.expect("* The name BoB is taken").await;
+    other.expect_closed().await;
+
+    bob.send("/rooms").await;
+    bob.expect("* Rooms: lobby (1)").await;
+    bob.close().await;
+
+    // The name is free again once its user left.
+    let mut other = LineClient::connect(server.address()).await;
+    other.expect(WELCOME).await;
+    other.send("BoB").await;
+    other.expect("* The room contains ").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn refuses_invalid_names_with_the_rules() {
+    let server = start().await;
+
+    for name in ["", "b@d", "bøb", "abcdefghijklmnopq"] {
+        let mut client = LineClient::connect(server.address()).await;
+        client.expect(WELCOME).await;
+        client.send(name).await;
+        client
+            .expect(&format!(
+                "* Invalid name: {name}, names are 1 to 16 ASCII letters or digits"
+            ))
+            .await;
+        client.expect_closed().await;
+    }
+
+    join(&server, "abcdefghijklmnop", "").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn accepts_the_configured_characters_and_length() {
+    let server = start_with(Config {
+        max_name_length: 20,
+        name_chars: "-_".to_string(),
+        ..Config::default()
+    })
+    .await;
+
+    let mut client = LineClient::connect(server.address()).await;
+    client.expect(WELCOME).await;
+    client.send("b.b").await;
+    client
+        .expect("* Invalid name: b.b, names are 1 to 20 ASCII letters, digits or any of -_")
+        .await;
+    client.expect_closed().await;
+
+    let mut client = join(&server, "bob_the-builder_2000", "").await;
+    client.send("/join no-dashes").await;
+    client.expect("* The room contains ").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn refuses_a_max_name_length_below_the_spec() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = Config {
+        max_name_length: 15,
+        ..Config::default()
+    };
+
+    let res = server::run(listener, config, std::future::pending::<()>()).await;
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn sends_private_messages_to_the_target_only() {
+    let server = start().await;
+
+    let mut bob = join(&server, "bob", "").await;
+    let mut alice = join(&server, "alice", "bob").await;
+    bob.expect("* alice has entered the room").await;
+    let mut carol = join(&server, "carol", "bob,alice").await;
+    bob.expect("* carol has entered the room").await;
+    alice.expect("* carol has entered the room").await;
+
+    // Private messages reach users in other rooms too.
+    carol.send("/join rust").await;
+    bob.expect("* carol has left the room").await;
+    alice.expect("* carol has left the room").await;
+    carol.expect("* The room contains ").await;
+
+    alice.send("/msg CAROL psst, it's me").await;
+    carol.expect("[alice -> carol] psst, it's me").await;
+    alice.send("hello everyone").await;
+    bob.expect("[alice] hello everyone").await;
+
+    alice.send("/msg dave hi").await;
+    alice.expect("* No user named dave").await;
+    alice.send("/msg carol").await;
+    alice.expect("* Usage: /msg <user> <text>").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn lists_members_and_sends_actions() {
+    let server = start().await;
+
+    let mut bob = join(&server, "bob", "").await;
+    let mut alice = join(&server, "alice", "bob").await;
+    bob.expect("* alice has entered the room").await;
+
+    alice.send("/who").await;
+    alice.expect("* In lobby: bob, alice").await;
+
+    alice.send("/me waves").await;
+    bob.expect("* alice waves").await;
+
+    alice.send("/leave").await;
+    alice.expect("* You left lobby").await;
+    bob.expect("* alice has left the room").await;
+    alice.send("/who").await;
+    alice.expect("* You are not in a room").await;
+    alice.send("/me waves").await;
+    alice.expect("* You are not in a room, /join one").await;
+
+    bob.send("/who").await;
+    bob.expect("* In lobby: bob").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn replays_the_last_messages_of_the_room_to_newcomers() {
+    let server = start_with(Config {
+        history_size: 2,
+        replay_history: true,
+        ..Config::default()
+    })
+    .await;
+
+    let mut bob = join(&server, "bob", "").await;
+    bob.send("one").await;
+    bob.send("two").await;
+    bob.send("/me counts").await;
+    bob.send("/who").await;
+    bob.expect("* In lobby: bob").await;
+
+    let mut alice = join(&server, "alice", "bob").await;
+    alice.expect("[bob] two").await;
+    alice.expect("* bob counts").await;
+    bob.expect("* alice has entered the room").await;
+
+    // Other rooms have their own history.
+    alice.send("/join rust").await;
+    alice.expect("* The room contains ").await;
+    alice.send("/who").await;
+    alice.expect("* In rust: alice").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn keeps_the_history_across_restarts() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("budget_chat_history.jsonl");
+    let _ = fs::remove_file(&path);
+    let config = Config {
+        history_size: 2,
+        replay_history: true,
+        history_file: Some(path.clone()),
+        ..Config::default()
+    };
+
+    let server = start_with(config.clone()).await;
+    let mut bob = join(&server, "bob", "").await;
+    for message in ["one", "two", "three"] {
+        bob.send(message).await;
+    }
+    bob.send("/who").await;
+    bob.expect("* In lobby: bob").await;
+    server.stop().await.unwrap();
+
+    // Everyone left, the restarted server still knows what was said.
+    let server = start_with(config).await;
+    let mut alice = join(&server, "alice", "").await;
+    alice.expect("[bob] two").await;
+    alice.expect("[bob] three").await;
+    server.stop().await.unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn keeps_the_history_of_the_rooms_talked_in_most_recently() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("budget_chat_history_rooms.jsonl");
+    let _ = fs::remove_file(&path);
+    let config = Config {
+        history_size: 2,
+        history_rooms: 2,
+        replay_history: true,
+        history_file: Some(path.clone()),
+        ..Config::default()
+    };
+
+    let server = start_with(config.clone()).await;
+    let mut bob = join(&server, "bob", "").await;
+    for room in ["one", "two", "three"] {
+        bob.send(&format!("/join {room}")).await;
+        bob.expect("* The room contains ").await;
+        for n in 1..=5 {
+            bob.send(&format!("{room} {n}")).await;
+        }
+    }
+    bob.send("/who").await;
+    bob.expect("* In three: bob").await;
+
+    // The file is rewritten once it holds twice what is kept.
+    assert!(fs::read_to_string(&path).unwrap().lines().count() < 8);
+    server.stop().await.unwrap();
+
+    let server = start_with(config).await;
+    let mut alice = join(&server, "alice", "").await;
+    for room in ["two", "three"] {
+        alice.send(&format!("/join {room}")).await;
+        alice.expect("* The room contains ").await;
+        alice.expect(&format!("[bob] {room} 4")).await;
+        alice.expect(&format!("[bob] {room} 5")).await;
+    }
+    // Nothing is left of the first room.
+    alice.send("/join one").await;
+    alice.expect("* The room contains ").await;
+    alice.send("/who").await;
+    alice.expect("* In one: alice").await;
+    server.stop().await.unwrap();
+
+    fs::remove_file(&path).unwrap();
+}
+
+/// Sends `count` numbered lines at once, more than a room buffers for the
+/// others.
+async fn flood(client: &mut LineClient, count: usize) {
+    let lines: Vec<_> = (1..=count).map(|n| n.to_string()).collect();
+    client.send(&lines.join("\n")).await;
+}
+
+#[tokio::test]
+async fn tells_users_how_many_messages_they_missed() {
+    let server = start_with(Config {
+        room_capacity: 2,
+        ..Config::default()
+    })
+    .await;
+
+    let mut bob = join(&server, "bob", "").await;
+    let mut alice = join(&server, "alice", "bob").await;
+    bob.expect("* alice has entered the room").await;
+
+    flood(&mut alice, 10).await;
+    bob.expect("* You missed 8 messages").await;
+    bob.expect("[alice] 9").await;
+    bob.expect("[alice] 10").await;
+
+    alice.send("still there?").await;
+    bob.expect("[alice] still there?").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn disconnects_users_that_fall_behind() {
+    let server = start_with(Config {
+        room_capacity: 2,
+        on_lag: OnLag::Disconnect,
+        ..Config::default()
+    })
+    .await;
+
+    let mut bob = join(&server, "bob", "").await;
+    let mut alice = join(&server, "alice", "bob").await;
+    bob.expect("* alice has entered the room").await;
+
+    flood(&mut alice, 10).await;
+    bob.expect("* You missed 8 messages and are disconnected")
+        .await;
+    bob.expect_closed().await;
+
+    alice.expect("* bob has left the room").await;
+    alice.send("/who").await;
+    alice.expect("* In lobby: alice").await;
+
+    server.stop().await.unwrap();
+}
@@ -0,0 +1,95 @@
+use crate::metrics::Metrics;
+
+use protohackers_core::{
+    metrics::{self, Registry},
+    ServerConfig,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::{net::SocketAddr, str, sync::Arc};
+use tokio::{net::UdpSocket, sync::mpsc};
+use tracing::info;
+
+pub async fn run(
+    socket: UdpSocket,
+    config: ServerConfig,
+    shutdown: impl Future,
+) -> crate::Result<()> {
+    let registry = Registry::new("unusual_database");
+    let metrics = Metrics::new(&registry);
+
+    let metrics_server = config
+        .bind_metrics()
+        .await?
+        .map(|listener| tokio::spawn(metrics::serve(listener, registry)));
+
+    let res = tokio::select! {
+        res = serve(socket, metrics) => res,
+        _ = shutdown => {
+            info!("shutting down");
+            Ok(())
+        }
+    };
+
+    if let Some(metrics_server) = metrics_server {
+        metrics_server.abort();
+    }
+
+    res
+}
+
+async fn serve(sock: UdpSocket, metrics: Metrics) -> crate::Result<()> {
+    info!("listening to new connections");
+
+    let r = Arc::new(sock);
+    let s = r.clone();
+    let (tx, mut rx) = mpsc::channel::<(Vec<u8>, SocketAddr)>(1_000);
+    let storage = Arc::new(Mutex::new(HashMap::<String, String>::new()));
+
+    let bytes_sent = metrics.bytes_sent.clone();
+    tokio::spawn(async move {
+        while let Some((bytes, addr)) = rx.recv().await {
+            if let Ok(n) = s.send_to(&bytes, &addr).await {
+                bytes_sent.inc_by(n as u64);
+            }
+        }
+    });
+
+    let mut buf = [0; 1024];
+    loop {
+        let (len, addr) = r.recv_from(&mut buf).await?;
+        metrics.bytes_received.inc_by(len as u64);
+        let message = match str::from_utf8(&buf[..len]) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+        info!("Message: {message}");
+        if message.contains("version") {
+            metrics.requests.with("version").inc();
+            let message = "version=gruberb 1.0".to_string();
+            tx.send((message.as_bytes().to_vec(), addr)).await?;
+        } else if let Some((mut key, value)) = message.split_once('=') {
+            metrics.requests.with("insert").inc();
+            if key.is_empty() {
+                key = " ";
+            }
+            storage
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.to_string());
+        } else {
+            metrics.requests.with("retrieve").inc();
+            let value = storage
+                .lock()
+                .unwrap()
+                .get(message)
+                .cloned()
+                .unwrap_or_default();
+            let message = format!("{message}={value}");
+            tx.send((message.as_bytes().to_vec(), addr)).await?;
+        }
+
+        buf.fill(0);
+    }
+}
@@ -0,0 +1,60 @@
+use problem_04::{server, DEFAULT_PORT};
+use protohackers_core::test_support::{TestServer, UdpClient};
+use protohackers_core::ServerConfig;
+
+async fn start() -> TestServer {
+    TestServer::start_udp(|socket, shutdown| {
+        server::run(socket, ServerConfig::new(DEFAULT_PORT, 0), shutdown)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn inserts_and_retrieves_values() {
+    let server = start().await;
+    let client = UdpClient::connect(server.address()).await;
+
+    client.send("foo=bar").await;
+    client.send("foo").await;
+    client.expect("foo=bar").await;
+
+    client.send("foo=bar=baz").await;
+    client.send("foo").await;
+    client.expect("foo=bar=baz").await;
+
+    client.send("empty=").await;
+    client.send("empty").await;
+    client.expect("empty=").await;
+
+    client.send("missing").await;
+    client.expect("missing=").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn shares_values_between_clients() {
+    let server = start().await;
+    let writer = UdpClient::connect(server.address()).await;
+    let reader = UdpClient::connect(server.address()).await;
+
+    writer.send("key=value").await;
+    writer.send("key").await;
+    writer.expect("key=value").await;
+
+    reader.send("key").await;
+    reader.expect("key=value").await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn reports_its_version() {
+    let server = start().await;
+    let client = UdpClient::connect(server.address()).await;
+
+    client.send("version").await;
+    client.expect("version=gruberb 1.0").await;
+
+    server.stop().await.unwrap();
+}
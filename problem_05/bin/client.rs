@@ -39,12 +39,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         while let Ok(Some(line)) = stdin_reader.next_line().await {
             info!("Received line from stdin: {}", line);
 
-            if let Err(_) = writer.write_all(line.as_bytes()).await {
+            if writer.write_all(line.as_bytes()).await.is_err() {
                 error!("Error reading from std");
                 break;
             }
 
-            let _ = writer.write_all(&[b'\n']).await;
+            let _ = writer.write_all(b"\n").await;
             let _ = writer.flush().await;
         }
     });
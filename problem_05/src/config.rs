@@ -0,0 +1,30 @@
+use protohackers_core::ServerConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{DEFAULT_PORT, MAX_CONNECTIONS};
+
+const UPSTREAM: &str = "206.189.113.124:16963";
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub server: ServerConfig,
+    /// `host:port` of the budget chat server every client is proxied to.
+    pub upstream: String,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            server: ServerConfig::new(DEFAULT_PORT, MAX_CONNECTIONS),
+            upstream: UPSTREAM.to_string(),
+        }
+    }
+}
+
+impl AsMut<ServerConfig> for Config {
+    fn as_mut(&mut self) -> &mut ServerConfig {
+        &mut self.server
+    }
+}
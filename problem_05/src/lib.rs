@@ -1,3 +1,15 @@
+mod config;
+pub use config::Config;
+
+mod metrics;
+
+pub mod server;
 mod strict_lines_codec;
 
-pub use strict_lines_codec::*;
\ No newline at end of file
+pub use strict_lines_codec::*;
+
+pub const MAX_CONNECTIONS: usize = 100;
+pub const DEFAULT_PORT: u16 = 1222;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,29 @@
+use protohackers_core::metrics::{Counter, CounterVec, Registry};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Metrics {
+    pub(crate) lines_proxied: CounterVec,
+    pub(crate) address_rewrites: Counter,
+    pub(crate) parse_errors: Counter,
+    pub(crate) bytes_received: Counter,
+    pub(crate) bytes_sent: Counter,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Metrics {
+        Metrics {
+            lines_proxied: registry.counter_vec(
+                "lines_proxied_total",
+                "Lines forwarded, by direction.",
+                "direction",
+            ),
+            address_rewrites: registry.counter(
+                "address_rewrites_total",
+                "Boguscoin addresses replaced in proxied lines.",
+            ),
+            parse_errors: registry.counter("parse_errors_total", "Lines that failed to decode."),
+            bytes_received: registry.counter("bytes_received_total", "Bytes read from clients."),
+            bytes_sent: registry.counter("bytes_sent_total", "Bytes written to clients."),
+        }
+    }
+}
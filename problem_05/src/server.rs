@@ -1,48 +1,63 @@
-use fancy_regex::Regex;
+use crate::{metrics::Metrics, Config, StrictLinesCodec};
+
+use fancy_regex::{Captures, Regex};
 use futures::{SinkExt, StreamExt};
-use problem_05::StrictLinesCodec;
+use protohackers_core::{metrics::Registry, ConnectionHandler, Server, Shutdown};
+use std::future::Future;
+use std::net::SocketAddr;
 use tokio::net::{TcpListener, TcpStream};
 use tokio_util::codec::{FramedRead, FramedWrite};
 use tracing::{error, info};
 
-const DEFAULT_IP: &str = "0.0.0.0";
-const DEFAULT_PORT: &str = "1222";
-
-const UPSTREAM_IP: &str = "206.189.113.124";
-const UPSTREAM_PORT: &str = "16963";
-
-type Error = Box<dyn std::error::Error + Send + Sync>;
-type Result<T> = std::result::Result<T, Error>;
-
-#[tokio::main]
-pub async fn main() -> Result<()> {
-    tracing_subscriber::fmt::try_init().expect("Cannot init tracing");
-
-    let listener = TcpListener::bind(&format!("{DEFAULT_IP}:{DEFAULT_PORT}")).await?;
-
-    info!("Start TCP server on {DEFAULT_IP}:{DEFAULT_PORT}");
+/// Proxies every client to the upstream budget chat server.
+struct MobInTheMiddle {
+    upstream: String,
+    metrics: Metrics,
+}
 
-    loop {
-        let (socket, address) = listener
-            .accept()
-            .await
-            .expect("Cannot establish connection");
+pub async fn run(
+    listener: TcpListener,
+    config: Config,
+    shutdown: impl Future,
+) -> crate::Result<()> {
+    info!("Start TCP server on {}", listener.local_addr()?);
+
+    let registry = Registry::new("mob_in_the_middle");
+    let mob_in_the_middle = MobInTheMiddle {
+        upstream: config.upstream,
+        metrics: Metrics::new(&registry),
+    };
+
+    Server::new(listener, mob_in_the_middle)
+        .max_connections(config.server.max_connections)
+        .metrics(registry, config.server.bind_metrics().await?)
+        .run(shutdown)
+        .await
+}
 
+impl ConnectionHandler for MobInTheMiddle {
+    async fn handle(
+        &self,
+        socket: TcpStream,
+        address: SocketAddr,
+        shutdown: Shutdown,
+    ) -> crate::Result<()> {
         info!("New request from: {address}");
 
-        let upstream = TcpStream::connect(&format!("{UPSTREAM_IP}:{UPSTREAM_PORT}"))
-            .await
-            .expect("Cannot establish upstream connection");
+        let upstream = TcpStream::connect(&self.upstream).await?;
 
-        info!("Connect to upstream on {UPSTREAM_IP}:{UPSTREAM_PORT}");
+        info!("Connect to upstream on {}", self.upstream);
 
-        tokio::spawn(async move {
-            let _ = handle_request(socket, upstream).await;
-        });
+        handle_request(socket, upstream, shutdown, &self.metrics).await
     }
 }
 
-pub async fn handle_request(socket: TcpStream, upstream: TcpStream) -> Result<()> {
+async fn handle_request(
+    socket: TcpStream,
+    upstream: TcpStream,
+    mut shutdown: Shutdown,
+    metrics: &Metrics,
+) -> crate::Result<()> {
     let (client_read, client_write) = socket.into_split();
     let mut framed_client_read = FramedRead::new(client_read, StrictLinesCodec::new());
     let mut framed_client_write = FramedWrite::new(client_write, StrictLinesCodec::new());
@@ -60,10 +75,13 @@ pub async fn handle_request(socket: TcpStream, upstream: TcpStream) -> Result<()
                         match response {
                             Ok(message) => {
                                 info!("Send upstream: {message}");
-                                let _ = framed_server_write.send(replace_address(message)).await;
+                                metrics.bytes_received.inc_by(message.len() as u64 + 1);
+                                metrics.lines_proxied.with("upstream").inc();
+                                let _ = framed_server_write.send(replace_address(message, metrics)).await;
                             }
                             Err(err) => {
                                 error!("Error reading from client: {err}");
+                                metrics.parse_errors.inc();
                                 return Err(err.into());
                             }
                         }
@@ -81,10 +99,14 @@ pub async fn handle_request(socket: TcpStream, upstream: TcpStream) -> Result<()
                         match response {
                             Ok(message) => {
                                 info!("Send to client: {message}");
-                                let _ = framed_client_write.send(replace_address(message)).await;
+                                let message = replace_address(message, metrics);
+                                metrics.bytes_sent.inc_by(message.len() as u64 + 1);
+                                metrics.lines_proxied.with("client").inc();
+                                let _ = framed_client_write.send(message).await;
                             }
                             Err(err) => {
                                 error!("Error reading from server: {err}");
+                                metrics.parse_errors.inc();
                                 return Err(err.into());
                             }
                         }
@@ -95,18 +117,27 @@ pub async fn handle_request(socket: TcpStream, upstream: TcpStream) -> Result<()
                     }
                 }
             }
+            _ = shutdown.recv() => {
+                info!("Shutdown");
+                break;
+            }
         }
     }
 
     Ok(())
 }
 
-fn replace_address(message: String) -> String {
+fn replace_address(message: String, metrics: &Metrics) -> String {
     let replacement = "7YWHMfk9JZe0LM0g1ZauHuiSxhI";
     let pattern = r"(?<= |^)7[a-zA-Z0-9]{25,34}(?= |$)";
     let re = Regex::new(pattern).unwrap();
 
-    let res = re.replace_all(&message, replacement).to_string();
+    let res = re
+        .replace_all(&message, |_: &Captures| {
+            metrics.address_rewrites.inc();
+            replacement
+        })
+        .to_string();
 
     info!("Replaced message: {res}");
 
@@ -1,5 +1,5 @@
 use bytes::{Buf, BufMut, BytesMut};
-use std::{cmp, fmt, io, str, usize};
+use std::{cmp, fmt, io, str};
 use tokio_util::codec::{Decoder, Encoder};
 
 /// A simple [`Decoder`] and [`Encoder`] implementation that splits up data into lines.
@@ -210,4 +210,4 @@ impl From<io::Error> for LinesCodecError {
     }
 }
 
-impl std::error::Error for LinesCodecError {}
\ No newline at end of file
+impl std::error::Error for LinesCodecError {}
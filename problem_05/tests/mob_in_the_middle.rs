@@ -0,0 +1,92 @@
+use problem_05::{server, Config};
+use protohackers_core::test_support::{LineClient, TestServer};
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+const TONY: &str = "7YWHMfk9JZe0LM0g1ZauHuiSxhI";
+
+/// Stands in for the budget chat server: greets every client with a message
+/// containing an address and echoes each line back with a name prefix.
+async fn start_upstream() -> SocketAddr {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (read, mut write) = socket.into_split();
+                let mut lines = BufReader::new(read).lines();
+
+                write
+                    .write_all(b"Send refunds to 7F1u3wSD5RbOHQmupo9nx4TnhQ\n")
+                    .await
+                    .unwrap();
+
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let echo = format!("[upstream] {line}\n");
+                    if write.write_all(echo.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    address
+}
+
+async fn start() -> TestServer {
+    let config = Config {
+        upstream: start_upstream().await.to_string(),
+        ..Config::default()
+    };
+
+    TestServer::start(|listener, shutdown| server::run(listener, config, shutdown)).await
+}
+
+#[tokio::test]
+async fn rewrites_addresses_in_both_directions() {
+    let server = start().await;
+    let mut client = LineClient::connect(server.address()).await;
+
+    client.expect(&format!("Send refunds to {TONY}")).await;
+
+    client
+        .send("Hi alice, please send payment to 7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX")
+        .await;
+    client
+        .expect(&format!(
+            "[upstream] Hi alice, please send payment to {TONY}"
+        ))
+        .await;
+
+    client
+        .send("7adNeSwJkMakpEcln9HEtthSRtxdmEHOT8T and 7LOrwbDlS8NujgjddyogWgIM93MV5N2VR")
+        .await;
+    client
+        .expect(&format!("[upstream] {TONY} and {TONY}"))
+        .await;
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn leaves_things_that_are_not_addresses_alone() {
+    let server = start().await;
+    let mut client = LineClient::connect(server.address()).await;
+
+    client.expect(&format!("Send refunds to {TONY}")).await;
+
+    for line in [
+        "too short 7F1u3wSD5RbOHQmupo9nx",
+        "too long 7F1u3wSD5RbOHQmupo9nx4TnhQ7F1u3wSD5RbOHQ",
+        "not at a word boundary x7F1u3wSD5RbOHQmupo9nx4TnhQ",
+        "product id 7F1u3wSD5RbOHQmupo9nx4TnhQ-1234",
+    ] {
+        client.send(line).await;
+        client.expect(&format!("[upstream] {line}")).await;
+    }
+
+    server.stop().await.unwrap();
+}
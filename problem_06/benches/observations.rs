@@ -0,0 +1,87 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use problem_06::observations::Timeline;
+
+const LIMIT: u64 = 60;
+
+/// `n` observations of one car on one road, one per minute at alternating
+/// miles, reported in shuffled order as cameras do not report in sync.
+fn workload(n: usize) -> Vec<(u16, u32)> {
+	let mut observations: Vec<_> = (0..n as u32).map(|i| ((i % 2) as u16, i * 60)).collect();
+
+	let mut state = 0x2545_f491_4f6c_dd1d_u64;
+	for i in (1..observations.len()).rev() {
+		state ^= state << 13;
+		state ^= state >> 7;
+		state ^= state << 17;
+		observations.swap(i, (state % (i as u64 + 1)) as usize);
+	}
+
+	observations
+}
+
+fn too_fast((mile1, timestamp1): (u16, u32), (mile2, timestamp2): (u16, u32)) -> bool {
+	let distance = mile1.abs_diff(mile2) as u64;
+	let time = timestamp1.abs_diff(timestamp2) as u64;
+
+	distance * 3600 > LIMIT * time
+}
+
+/// Compares every observation against all earlier ones, as before the
+/// observations were kept in order.
+fn linear_scan(workload: &[(u16, u32)]) -> usize {
+	let mut observations = Vec::new();
+	let mut tickets = 0;
+
+	for &observation in workload {
+		tickets += observations
+			.iter()
+			.filter(|&&earlier| too_fast(earlier, observation))
+			.count();
+		observations.push(observation);
+	}
+
+	tickets
+}
+
+fn timeline(workload: &[(u16, u32)]) -> usize {
+	let mut timeline = Timeline::default();
+	let mut tickets = 0;
+
+	for &(mile, timestamp) in workload {
+		tickets += timeline
+			.insert(mile, timestamp, None)
+			.iter()
+			.filter(|neighbour| too_fast((neighbour.mile, neighbour.timestamp), (mile, timestamp)))
+			.count();
+	}
+
+	tickets
+}
+
+fn observations(c: &mut Criterion) {
+	let mut group = c.benchmark_group("observations");
+	group.sample_size(10);
+
+	for n in [1_000, 10_000, 1_000_000] {
+		let workload = workload(n);
+		group.throughput(Throughput::Elements(n as u64));
+
+		group.bench_with_input(BenchmarkId::new("timeline", n), &workload, |b, workload| {
+			b.iter(|| timeline(black_box(workload)))
+		});
+
+		// Quadratic, a million observations would take hours.
+		if n <= 10_000 {
+			group.bench_with_input(
+				BenchmarkId::new("linear_scan", n),
+				&workload,
+				|b, workload| b.iter(|| linear_scan(black_box(workload))),
+			);
+		}
+	}
+
+	group.finish();
+}
+
+criterion_group!(benches, observations);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+//! Prints the records of a ticket audit log as JSON lines, optionally only
+//! those of one plate, road or day:
+//!
+//! ```text
+//! speed-daemon-audit audit.jsonl --plate UN1X --day 0
+//! ```
+
+use std::{env, path::PathBuf, process};
+
+use problem_06::{
+	audit::{self, AuditRecord},
+	policy::SECONDS_PER_DAY,
+};
+
+const USAGE: &str = "usage: speed-daemon-audit <FILE> [--plate PLATE] [--road ROAD] [--day DAY]";
+
+#[derive(Debug, Default)]
+struct Filter {
+	plate: Option<String>,
+	road: Option<u16>,
+	day: Option<u32>,
+}
+
+impl Filter {
+	fn matches(&self, record: &AuditRecord) -> bool {
+		let days = record.timestamp1 / SECONDS_PER_DAY..=record.timestamp2 / SECONDS_PER_DAY;
+
+		self.plate
+			.as_ref()
+			.is_none_or(|plate| *plate == record.plate)
+			&& self.road.is_none_or(|road| road == record.road)
+			&& self.day.is_none_or(|day| days.contains(&day))
+	}
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(PathBuf, Filter), String> {
+	let mut path = None;
+	let mut filter = Filter::default();
+
+	while let Some(arg) = args.next() {
+		let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+		match arg.as_str() {
+			"--plate" => filter.plate = Some(value()?),
+			"--road" => filter.road = Some(value()?.parse().map_err(|_| "invalid road")?),
+			"--day" => filter.day = Some(value()?.parse().map_err(|_| "invalid day")?),
+			_ if path.is_none() && !arg.starts_with('-') => path = Some(PathBuf::from(arg)),
+			_ => return Err(format!("unexpected argument {arg}")),
+		}
+	}
+
+	Ok((path.ok_or("missing audit log file")?, filter))
+}
+
+fn main() -> problem_06::Result<()> {
+	let (path, filter) = parse_args(env::args().skip(1)).unwrap_or_else(|e| {
+		eprintln!("{e}\n{USAGE}");
+		process::exit(2);
+	});
+
+	for record in audit::read(&path)? {
+		if filter.matches(&record) {
+			println!("{}", serde_json::to_string(&record)?);
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,389 @@
+//! Puts a running speed daemon under load and checks its tickets:
+//!
+//! ```text
+//! speed-daemon-load --address 127.0.0.1:1222 --roads 200 --cameras 10 --cars 20
+//! ```
+//!
+//! Every road has `--cameras` cameras ten miles apart and `--cars` cars that
+//! pass all of them within one day, `--speeders` percent of them too fast.
+//! Each camera reports from its own connection, all at the same time, to
+//! dispatchers that split the roads between them. Each speeder must get
+//! exactly one ticket, nobody else any, and the time from sending the later
+//! of the two observations to receiving the ticket is reported.
+
+use std::{
+	collections::HashMap,
+	env,
+	net::SocketAddr,
+	process,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use problem_06::{
+	frame::parse_frame, policy::SECONDS_PER_DAY, ClientFrames, Encode, ServerFrames, DEFAULT_PORT,
+};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::TcpStream,
+	sync::mpsc,
+	task::JoinSet,
+	time,
+};
+
+const USAGE: &str = "usage: speed-daemon-load [--address ADDRESS] [--roads N] [--cameras N] \
+	[--cars N] [--dispatchers N] [--speeders PERCENT] [--timeout SECONDS]";
+
+const LIMIT: u16 = 60;
+const MILES_BETWEEN_CAMERAS: u16 = 10;
+
+/// A dispatcher frame holds at most 255 roads.
+const MAX_ROADS_PER_DISPATCHER: usize = 255;
+
+/// Keeps the slowest car within one day from its start.
+const MAX_CAMERAS: u16 = 50;
+
+#[derive(Debug)]
+struct Args {
+	address: SocketAddr,
+	roads: u16,
+	cameras: u16,
+	cars: u32,
+	dispatchers: usize,
+	speeders: u32,
+	timeout: Duration,
+}
+
+impl Default for Args {
+	fn default() -> Args {
+		Args {
+			address: SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)),
+			roads: 100,
+			cameras: 10,
+			cars: 20,
+			dispatchers: 10,
+			speeders: 20,
+			timeout: Duration::from_secs(30),
+		}
+	}
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+	fn number<T: std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+		value
+			.ok_or(format!("{arg} needs a value"))?
+			.parse()
+			.map_err(|_| format!("invalid value for {arg}"))
+	}
+
+	let mut parsed = Args::default();
+
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--address" => parsed.address = number(&arg, args.next())?,
+			"--roads" => parsed.roads = number(&arg, args.next())?,
+			"--cameras" => parsed.cameras = number(&arg, args.next())?,
+			"--cars" => parsed.cars = number(&arg, args.next())?,
+			"--dispatchers" => parsed.dispatchers = number(&arg, args.next())?,
+			"--speeders" => parsed.speeders = number(&arg, args.next())?,
+			"--timeout" => parsed.timeout = Duration::from_secs(number(&arg, args.next())?),
+			_ => return Err(format!("unexpected argument {arg}")),
+		}
+	}
+
+	if parsed.roads == 0 || parsed.dispatchers == 0 {
+		return Err("--roads and --dispatchers must be at least 1".to_string());
+	}
+	if !(2..=MAX_CAMERAS).contains(&parsed.cameras) {
+		return Err(format!("--cameras must be between 2 and {MAX_CAMERAS}"));
+	}
+	if parsed.roads as usize > parsed.dispatchers * MAX_ROADS_PER_DISPATCHER {
+		return Err(format!(
+			"a dispatcher takes at most {MAX_ROADS_PER_DISPATCHER} roads, use more --dispatchers"
+		));
+	}
+	if parsed.speeders > 100 {
+		return Err("--speeders is a percentage".to_string());
+	}
+
+	Ok(parsed)
+}
+
+/// A car driving the whole road at a constant speed.
+#[derive(Debug, Clone)]
+struct Car {
+	plate: String,
+	speed: u16,
+	start: u32,
+}
+
+impl Car {
+	fn new(road: u16, car: u32, speeders: u32) -> Car {
+		// Spreads the speeders evenly over every hundred cars.
+		let speeder = (car * 37) % 100 < speeders;
+		// Speeders are at least 10 mph too fast, the others at least 5 mph
+		// too slow, so rounding timestamps to seconds does not matter.
+		let speed = if speeder {
+			LIMIT + 10 + (car % 30) as u16
+		} else {
+			LIMIT - 5 - (car % 15) as u16
+		};
+		let day = (road as u32 + car) % 3;
+
+		Car {
+			plate: format!("R{road}C{car}"),
+			speed,
+			start: day * SECONDS_PER_DAY + (car * 37) % 40_000,
+		}
+	}
+
+	fn is_speeder(&self) -> bool {
+		self.speed > LIMIT
+	}
+
+	fn day(&self) -> u32 {
+		self.start / SECONDS_PER_DAY
+	}
+
+	fn timestamp_at(&self, mile: u16) -> u32 {
+		self.start + (mile as u32 * 3600) / self.speed as u32
+	}
+}
+
+/// When each observation was sent, by plate and mile.
+type Sent = Arc<Mutex<HashMap<(String, u16), Instant>>>;
+
+#[derive(Debug)]
+struct Received {
+	plate: String,
+	day: u32,
+	latency: Option<Duration>,
+}
+
+async fn camera(
+	address: SocketAddr,
+	road: u16,
+	mile: u16,
+	cars: Arc<Vec<Car>>,
+	sent: Sent,
+) -> problem_06::Result<()> {
+	let mut stream = TcpStream::connect(address).await?;
+	let limit = LIMIT;
+	stream
+		.write_all(&ClientFrames::IAmCamera { road, mile, limit }.to_bytes()?)
+		.await?;
+
+	for car in cars.iter() {
+		let frame = ClientFrames::Plate {
+			plate: car.plate.clone(),
+			timestamp: car.timestamp_at(mile),
+		};
+		sent.lock()
+			.unwrap()
+			.insert((car.plate.clone(), mile), Instant::now());
+		stream.write_all(&frame.to_bytes()?).await?;
+	}
+
+	stream.shutdown().await?;
+	// Wait for the server to close the connection, it read everything then.
+	stream.read_to_end(&mut Vec::new()).await?;
+
+	Ok(())
+}
+
+/// Connects a dispatcher and returns once the server registered it.
+async fn connect_dispatcher(address: SocketAddr, roads: Vec<u16>) -> problem_06::Result<TcpStream> {
+	let mut stream = TcpStream::connect(address).await?;
+	stream
+		.write_all(&ClientFrames::IAmDispatcher { roads }.to_bytes()?)
+		.await?;
+	stream
+		.write_all(&ClientFrames::WantHeartbeat { interval: 1 }.to_bytes()?)
+		.await?;
+
+	// Frames are handled in order, the first heartbeat comes after the
+	// registration.
+	let mut buffer = BytesMut::new();
+	while parse_frame::<ServerFrames>(&mut buffer)?.is_none() {
+		if stream.read_buf(&mut buffer).await? == 0 {
+			return Err("server closed the dispatcher connection".into());
+		}
+	}
+
+	Ok(stream)
+}
+
+async fn dispatcher(
+	mut stream: TcpStream,
+	sent: Sent,
+	received: mpsc::UnboundedSender<Received>,
+) -> problem_06::Result<()> {
+	let mut buffer = BytesMut::new();
+
+	loop {
+		while let Some(frame) = parse_frame::<ServerFrames>(&mut buffer)? {
+			let ServerFrames::Ticket {
+				plate,
+				mile1,
+				timestamp1,
+				mile2,
+				..
+			} = frame
+			else {
+				continue;
+			};
+
+			let sent_at = sent.lock().unwrap();
+			let latency = [mile1, mile2]
+				.into_iter()
+				.map(|mile| sent_at.get(&(plate.clone(), mile)).copied())
+				.collect::<Option<Vec<_>>>()
+				.and_then(|instants| instants.into_iter().max())
+				.map(|instant| instant.elapsed());
+
+			let day = timestamp1 / SECONDS_PER_DAY;
+			if received
+				.send(Received {
+					plate,
+					day,
+					latency,
+				})
+				.is_err()
+			{
+				return Ok(());
+			}
+		}
+
+		if stream.read_buf(&mut buffer).await? == 0 {
+			return Err("server closed the dispatcher connection".into());
+		}
+	}
+}
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+	if sorted.is_empty() {
+		return Duration::ZERO;
+	}
+	sorted[(sorted.len() - 1) * p / 100]
+}
+
+#[tokio::main]
+async fn main() -> problem_06::Result<()> {
+	let args = parse_args(env::args().skip(1)).unwrap_or_else(|e| {
+		eprintln!("{e}\n{USAGE}");
+		process::exit(2);
+	});
+
+	let roads: Vec<u16> = (1..=args.roads).collect();
+	let sent = Sent::default();
+	let (received_tx, mut received_rx) = mpsc::unbounded_channel();
+
+	let mut dispatchers = JoinSet::new();
+	for i in 0..args.dispatchers {
+		let roads: Vec<u16> = roads
+			.iter()
+			.copied()
+			.filter(|road| *road as usize % args.dispatchers == i)
+			.collect();
+		let stream = connect_dispatcher(args.address, roads).await?;
+		dispatchers.spawn(dispatcher(stream, sent.clone(), received_tx.clone()));
+	}
+	drop(received_tx);
+
+	let mut expected = HashMap::new();
+	let mut cameras = JoinSet::new();
+	let started = Instant::now();
+
+	for &road in &roads {
+		let cars: Arc<Vec<Car>> = Arc::new(
+			(0..args.cars)
+				.map(|car| Car::new(road, car, args.speeders))
+				.collect(),
+		);
+		for car in cars.iter().filter(|car| car.is_speeder()) {
+			expected.insert((car.plate.clone(), car.day()), 0);
+		}
+
+		for camera_number in 0..args.cameras {
+			let mile = camera_number * MILES_BETWEEN_CAMERAS;
+			cameras.spawn(camera(args.address, road, mile, cars.clone(), sent.clone()));
+		}
+	}
+
+	let camera_count = cameras.len();
+	while let Some(res) = cameras.join_next().await {
+		res??;
+	}
+	let elapsed = started.elapsed();
+	let observations = camera_count * args.cars as usize;
+
+	let mut latencies = Vec::new();
+	let mut unexpected = 0;
+	let mut outstanding = expected.len();
+
+	let deadline = time::sleep(args.timeout);
+	tokio::pin!(deadline);
+
+	while outstanding > 0 {
+		let received = tokio::select! {
+			received = received_rx.recv() => match received {
+				Some(received) => received,
+				None => break,
+			},
+			_ = &mut deadline => break,
+		};
+
+		match expected.get_mut(&(received.plate, received.day)) {
+			Some(count) => {
+				*count += 1;
+				if *count == 1 {
+					outstanding -= 1;
+				}
+			}
+			None => unexpected += 1,
+		}
+		latencies.extend(received.latency);
+	}
+
+	// Anything else that arrives shortly after is a duplicate or unexpected.
+	while let Ok(Some(received)) =
+		time::timeout(Duration::from_millis(500), received_rx.recv()).await
+	{
+		match expected.get_mut(&(received.plate, received.day)) {
+			Some(count) => *count += 1,
+			None => unexpected += 1,
+		}
+	}
+	dispatchers.abort_all();
+
+	let missing = expected.values().filter(|count| **count == 0).count();
+	let duplicated = expected.values().filter(|count| **count > 1).count();
+	latencies.sort();
+
+	println!(
+		"{} cameras and {} dispatchers sent {observations} observations in {:.2?} ({:.0}/s)",
+		camera_count,
+		args.dispatchers,
+		elapsed,
+		observations as f64 / elapsed.as_secs_f64()
+	);
+	println!(
+		"tickets: {} expected, {} missing, {duplicated} duplicated, {unexpected} unexpected",
+		expected.len(),
+		missing
+	);
+	println!(
+		"latency: p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}",
+		percentile(&latencies, 50),
+		percentile(&latencies, 90),
+		percentile(&latencies, 99),
+		latencies.last().copied().unwrap_or_default()
+	);
+
+	if missing > 0 || duplicated > 0 || unexpected > 0 {
+		process::exit(1);
+	}
+
+	Ok(())
+}
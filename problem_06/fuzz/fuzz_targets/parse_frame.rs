@@ -0,0 +1,42 @@
+//! Feeds arbitrary byte streams to the frame parser, split into reads at
+//! arbitrary points.
+//!
+//! The first byte of the input picks the read size, the rest is the stream.
+//! Parsing must never panic and must yield the same frames however the
+//! stream is split.
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use problem_06::frame::{parse_frame, ClientFrames};
+
+fn parse_chunked(bytes: &[u8], chunk_size: usize) -> (Vec<ClientFrames>, bool) {
+	let mut buffer = BytesMut::new();
+	let mut frames = Vec::new();
+
+	for chunk in bytes.chunks(chunk_size) {
+		buffer.extend_from_slice(chunk);
+
+		loop {
+			match parse_frame(&mut buffer) {
+				Ok(Some(frame)) => frames.push(frame),
+				Ok(None) => break,
+				Err(_) => return (frames, true),
+			}
+		}
+	}
+
+	(frames, false)
+}
+
+fuzz_target!(|data: &[u8]| {
+	let Some((&chunk_size, stream)) = data.split_first() else {
+		return;
+	};
+
+	let whole = parse_chunked(stream, stream.len().max(1));
+	let split = parse_chunked(stream, chunk_size.max(1) as usize);
+
+	assert_eq!(whole, split);
+});
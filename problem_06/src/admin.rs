@@ -0,0 +1,166 @@
+//! An HTTP/JSON API for operators to look into the state of the daemon and
+//! handle tickets nobody could be sent:
+//!
+//! - `GET /roads`: cameras, dispatchers and the number of open tickets by road
+//! - `GET /open-tickets`: the tickets waiting for a dispatcher
+//! - `GET /observations?plate=UN1X&limit=20`: the latest observations of a plate
+//! - `GET /tickets-per-day`: how many tickets were issued for each day since
+//!   the start, by the day of their first observation
+//! - `POST /open-tickets/dispatch?road=123&plate=UN1X&timestamp1=0`: tries to
+//!   send an open ticket to a dispatcher again
+//! - `POST /open-tickets/drop?road=123&plate=UN1X&timestamp1=0`: drops an open
+//!   ticket
+
+use std::sync::Arc;
+
+use protohackers_core::http::{self, Request, Response};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use crate::{
+	audit::{AuditRecord, Event},
+	db::{Db, PlateName, Road},
+	ticketing::dispatch,
+};
+
+/// How many observations `GET /observations` returns without a `limit`.
+const DEFAULT_OBSERVATIONS: usize = 100;
+
+/// Serves the API on `listener` until the task is aborted.
+pub(crate) async fn serve(listener: TcpListener, db: Arc<Db>) -> crate::Result<()> {
+	http::serve(listener, move |request: Request| {
+		let db = db.clone();
+		async move {
+			match handle(&db, &request).await {
+				Ok(body) => Response::new(200, "application/json", format!("{body}\n")),
+				Err(response) => response,
+			}
+		}
+	})
+	.await
+}
+
+async fn handle(db: &Db, request: &Request) -> Result<Value, Response> {
+	match (request.method.as_str(), request.path.as_str()) {
+		("GET", "/roads") => Ok(db
+			.snapshot()
+			.into_iter()
+			.map(|road| {
+				let cameras: Vec<_> = road
+					.cameras
+					.iter()
+					.map(|(id, camera)| {
+						json!({
+							"address": id.0.to_string(),
+							"mile": camera.mile.0,
+							"limit": camera.limit.0,
+						})
+					})
+					.collect();
+				let dispatchers: Vec<_> =
+					road.dispatchers.iter().map(|id| id.0.to_string()).collect();
+
+				json!({
+					"road": road.road.0,
+					"cameras": cameras,
+					"dispatchers": dispatchers,
+					"open_tickets": road.open_tickets.len(),
+				})
+			})
+			.collect()),
+		("GET", "/open-tickets") => Ok(json!(db
+			.snapshot()
+			.into_iter()
+			.flat_map(|road| road.open_tickets)
+			.collect::<Vec<_>>())),
+		("GET", "/observations") => {
+			let plate = param(request, "plate")?;
+			let limit = match param(request, "limit") {
+				Ok(limit) => limit
+					.parse()
+					.map_err(|_| bad_request("`limit` must be a number"))?,
+				Err(_) => DEFAULT_OBSERVATIONS,
+			};
+
+			Ok(db
+				.observations(&PlateName(plate.to_string()))
+				.into_iter()
+				.take(limit)
+				.map(|(road, observation)| {
+					json!({
+						"road": road.0,
+						"mile": observation.mile,
+						"timestamp": observation.timestamp,
+					})
+				})
+				.collect())
+		}
+		("GET", "/tickets-per-day") => Ok(db
+			.tickets_per_day()
+			.into_iter()
+			.map(|(day, tickets)| json!({ "day": day, "tickets": tickets }))
+			.collect()),
+		("POST", "/open-tickets/dispatch") => {
+			let (road, plate, timestamp1) = ticket_params(request)?;
+			let ticket = db
+				.take_open_ticket(&road, plate, timestamp1)
+				.ok_or_else(no_such_ticket)?;
+
+			// Without a dispatcher for the road it is an open ticket again.
+			dispatch(db, ticket.clone()).await;
+			Ok(json!(ticket))
+		}
+		("POST", "/open-tickets/drop") => {
+			let (road, plate, timestamp1) = ticket_params(request)?;
+			let ticket = db
+				.drop_open_ticket(&road, plate, timestamp1)
+				.ok_or_else(no_such_ticket)?;
+			db.audit(AuditRecord::new(Event::Dropped, &ticket));
+
+			Ok(json!(ticket))
+		}
+		_ => Err(Response::not_found()),
+	}
+}
+
+/// The value of the query parameter `name`, plates and numbers need no
+/// percent-decoding.
+fn param<'a>(request: &'a Request, name: &str) -> Result<&'a str, Response> {
+	request
+		.query
+		.split('&')
+		.filter_map(|pair| pair.split_once('='))
+		.find(|(key, _)| *key == name)
+		.map(|(_, value)| value)
+		.ok_or_else(|| bad_request(&format!("missing `{name}`")))
+}
+
+/// The road, plate and first timestamp that identify an open ticket, a car
+/// gets at most one ticket a day.
+fn ticket_params(request: &Request) -> Result<(Road, &str, u32), Response> {
+	let road = param(request, "road")?
+		.parse()
+		.map_err(|_| bad_request("`road` must be a number"))?;
+	let plate = param(request, "plate")?;
+	let timestamp1 = param(request, "timestamp1")?
+		.parse()
+		.map_err(|_| bad_request("`timestamp1` must be a number"))?;
+
+	Ok((Road(road), plate, timestamp1))
+}
+
+fn bad_request(error: &str) -> Response {
+	Response::new(
+		400,
+		"application/json",
+		format!("{}\n", json!({ "error": error })),
+	)
+}
+
+fn no_such_ticket() -> Response {
+	Response::new(
+		404,
+		"application/json",
+		format!("{}\n", json!({ "error": "no such open ticket" })),
+	)
+}
@@ -0,0 +1,156 @@
+//! A durable record of every ticket and what became of it, for disputes.
+//!
+//! Each event is one [`AuditRecord`], appended as a JSON line or a CSV row.
+//! A ticket shows up once as [`Event::Issued`], with the cameras that saw the
+//! car, and then once for every attempt to deliver it.
+
+use std::{
+	fs::{File, OpenOptions},
+	io::{self, BufRead, BufReader, Write},
+	path::Path,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::Ticket;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditFormat {
+	/// One JSON object per line.
+	#[default]
+	Jsonl,
+	/// Comma separated values with a header row.
+	Csv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+	/// Two observations were too fast apart.
+	Issued,
+	/// Handed to the dispatcher in `dispatcher`.
+	Dispatched,
+	/// No dispatcher for the road, kept until one connects.
+	Queued,
+	/// An operator dropped the queued ticket.
+	Dropped,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditRecord {
+	/// Seconds since the Unix epoch when the event happened.
+	pub logged_at: u64,
+	pub event: Event,
+	pub plate: String,
+	pub road: u16,
+	pub mile1: u16,
+	pub timestamp1: u32,
+	/// The camera that saw the plate at `timestamp1`, only for
+	/// [`Event::Issued`] and unless the observation came from an older
+	/// journal.
+	pub camera1: Option<String>,
+	pub mile2: u16,
+	pub timestamp2: u32,
+	/// The camera that saw the plate at `timestamp2`, like `camera1`.
+	pub camera2: Option<String>,
+	/// Hundredths of a mile per hour.
+	pub speed: u16,
+	/// Only for [`Event::Dispatched`].
+	pub dispatcher: Option<String>,
+}
+
+impl AuditRecord {
+	pub(crate) fn new(event: Event, ticket: &Ticket) -> AuditRecord {
+		AuditRecord {
+			logged_at: SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map_or(0, |elapsed| elapsed.as_secs()),
+			event,
+			plate: ticket.plate.clone(),
+			road: ticket.road,
+			mile1: ticket.mile1,
+			timestamp1: ticket.timestamp1,
+			camera1: None,
+			mile2: ticket.mile2,
+			timestamp2: ticket.timestamp2,
+			camera2: None,
+			speed: ticket.speed,
+			dispatcher: None,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+	file: File,
+	format: AuditFormat,
+}
+
+impl AuditLog {
+	/// Opens the log at `path` to append to it, creating it if needed.
+	pub(crate) fn open(path: &Path, format: AuditFormat) -> io::Result<AuditLog> {
+		let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+		if format == AuditFormat::Csv && file.metadata()?.len() == 0 {
+			let mut writer = csv::Writer::from_writer(Vec::new());
+			writer.write_record([
+				"logged_at",
+				"event",
+				"plate",
+				"road",
+				"mile1",
+				"timestamp1",
+				"camera1",
+				"mile2",
+				"timestamp2",
+				"camera2",
+				"speed",
+				"dispatcher",
+			])?;
+			file.write_all(&writer.into_inner().map_err(|e| e.into_error())?)?;
+		}
+
+		Ok(AuditLog { file, format })
+	}
+
+	/// Writes `record` with a single write, like the journal.
+	pub(crate) fn append(&mut self, record: &AuditRecord) -> io::Result<()> {
+		let line = match self.format {
+			AuditFormat::Jsonl => {
+				let mut line = serde_json::to_vec(record)?;
+				line.push(b'\n');
+				line
+			}
+			AuditFormat::Csv => {
+				let mut writer = csv::WriterBuilder::new()
+					.has_headers(false)
+					.from_writer(Vec::new());
+				writer.serialize(record)?;
+				writer.into_inner().map_err(|e| e.into_error())?
+			}
+		};
+
+		self.file.write_all(&line)
+	}
+}
+
+/// Reads every record of the log at `path`, in either format.
+pub fn read(path: &Path) -> crate::Result<Vec<AuditRecord>> {
+	let mut reader = BufReader::new(File::open(path)?);
+
+	let is_json = reader.fill_buf()?.first() == Some(&b'{');
+	if is_json {
+		return reader
+			.lines()
+			.filter(|line| !line.as_ref().is_ok_and(|line| line.is_empty()))
+			.map(|line| Ok(serde_json::from_str(&line?)?))
+			.collect();
+	}
+
+	csv::Reader::from_reader(reader)
+		.deserialize()
+		.map(|record| Ok(record?))
+		.collect()
+}
@@ -0,0 +1,34 @@
+use std::{sync::Arc, time::Duration};
+
+use tracing::info;
+
+use crate::{db::Db, metrics::Metrics};
+
+/// Compacts `db` every `interval` until the task is aborted and publishes
+/// the size of the held state.
+pub(crate) async fn run(
+	db: Arc<Db>,
+	retention_days: Option<u32>,
+	interval: Duration,
+	metrics: Metrics,
+) {
+	let mut interval = tokio::time::interval(interval);
+
+	loop {
+		interval.tick().await;
+
+		let (held, evicted) = db.compact(retention_days);
+		if evicted != Default::default() {
+			info!("Compaction dropped {evicted:?}, holding {held:?}");
+		}
+
+		metrics.observations_held.set(held.observations as i64);
+		metrics.ticketed_days_held.set(held.ticketed_days as i64);
+		metrics
+			.observations_evicted
+			.inc_by(evicted.observations as u64);
+		metrics
+			.ticketed_days_evicted
+			.inc_by(evicted.ticketed_days as u64);
+	}
+}
@@ -0,0 +1,85 @@
+use std::{
+	net::{IpAddr, Ipv4Addr},
+	path::PathBuf,
+};
+
+use protohackers_core::ServerConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{AuditFormat, DEFAULT_PORT, MAX_CONNECTIONS};
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+	#[serde(flatten)]
+	pub server: ServerConfig,
+	/// Append-only file that observations, ticketed days and undelivered
+	/// tickets are written to and replayed from on startup. Without it the
+	/// state is lost on restart. Every compaction rewrites it with only the
+	/// state that is still held.
+	pub journal: Option<PathBuf>,
+	/// Which of several dispatchers for a road gets a ticket.
+	pub dispatch: Dispatch,
+	/// Days of observations kept before the latest one on the same road.
+	/// Once compacted away, older observations are ignored and can no
+	/// longer lead to a ticket. Keeps everything if unset.
+	pub retention_days: Option<u32>,
+	/// Seconds between two runs of the task that drops observations outside
+	/// the retention and updates the stats of the held state.
+	pub compaction_interval_secs: u64,
+	/// Serves the HTTP/JSON admin API on this port of `admin_ip`.
+	pub admin_port: Option<u16>,
+	/// Address of the admin API, loopback by default as the API has no
+	/// authentication and can drop and dispatch tickets.
+	pub admin_ip: IpAddr,
+	/// File every issued ticket and its delivery is appended to.
+	pub audit_log: Option<PathBuf>,
+	pub audit_format: AuditFormat,
+	/// Seconds a client may send nothing before it is disconnected, without a
+	/// limit if unset. Dispatchers and cameras on quiet roads idle a lot.
+	pub idle_timeout_secs: Option<u64>,
+	/// Seconds a client has to finish a frame once it sent the first byte.
+	pub frame_timeout_secs: u64,
+	/// Seconds a write to a client may take.
+	pub write_timeout_secs: u64,
+	/// Frames queued for a client, a dispatcher that lets the queue fill up
+	/// is disconnected and its tickets go to another dispatcher.
+	pub outbound_queue: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dispatch {
+	/// The dispatcher that connected first, the others only take over once it
+	/// disconnects.
+	#[default]
+	First,
+	/// Each dispatcher in turn.
+	RoundRobin,
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		Config {
+			server: ServerConfig::new(DEFAULT_PORT, MAX_CONNECTIONS),
+			journal: None,
+			dispatch: Dispatch::default(),
+			retention_days: None,
+			compaction_interval_secs: 60,
+			admin_port: None,
+			admin_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+			audit_log: None,
+			audit_format: AuditFormat::default(),
+			idle_timeout_secs: None,
+			frame_timeout_secs: 10,
+			write_timeout_secs: 10,
+			outbound_queue: 1024,
+		}
+	}
+}
+
+impl AsMut<ServerConfig> for Config {
+	fn as_mut(&mut self) -> &mut ServerConfig {
+		&mut self.server
+	}
+}
@@ -1,78 +1,206 @@
-use std::{io::Cursor, net::SocketAddr};
+use std::{fmt, net::SocketAddr, sync::Arc};
 
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 use tokio::{
 	io::{AsyncReadExt, AsyncWriteExt, BufWriter},
 	net::TcpStream,
+	sync::{
+		mpsc::{self, error::TrySendError},
+		Notify,
+	},
+	time::{self, Duration, Instant},
 };
 
-use crate::frame::{self, ClientFrames, ServerFrames};
+use crate::{
+	db::Camera,
+	frame::{self, ClientFrames, Encode, FrameError, ServerFrames},
+	metrics::Metrics,
+};
 
 #[derive(PartialEq)]
 pub(crate) enum ConnectionType {
-	Camera,
+	Camera(Camera),
 	Dispatcher,
 }
 
+/// How long the server waits on a client, see [`Config`](crate::Config).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timeouts {
+	pub(crate) idle: Option<Duration>,
+	pub(crate) frame: Duration,
+	pub(crate) write: Duration,
+}
+
+/// Why a client was disconnected without breaking the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SlowClient {
+	/// Sent nothing for the idle timeout.
+	Idle,
+	/// Started a frame and did not finish it within the frame timeout.
+	PartialFrame,
+	/// Did not take a write within the write timeout.
+	Write,
+	/// Let its queue of outbound frames fill up.
+	Lagging,
+}
+
+impl SlowClient {
+	pub(crate) fn label(&self) -> &'static str {
+		match self {
+			SlowClient::Idle => "idle",
+			SlowClient::PartialFrame => "partial_frame",
+			SlowClient::Write => "write",
+			SlowClient::Lagging => "lagging",
+		}
+	}
+}
+
+impl fmt::Display for SlowClient {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SlowClient::Idle => write!(f, "idle for too long"),
+			SlowClient::PartialFrame => write!(f, "frame not finished in time"),
+			SlowClient::Write => write!(f, "not reading"),
+			SlowClient::Lagging => write!(f, "too far behind"),
+		}
+	}
+}
+
+impl std::error::Error for SlowClient {}
+
+/// The frames waiting to be written to a client.
+#[derive(Debug, Clone)]
+pub(crate) struct Outbound {
+	sender: mpsc::Sender<ServerFrames>,
+	lagging: Arc<Notify>,
+}
+
+impl Outbound {
+	pub(crate) fn new(capacity: usize) -> (Outbound, mpsc::Receiver<ServerFrames>) {
+		let (sender, receiver) = mpsc::channel(capacity.max(1));
+		let outbound = Outbound {
+			sender,
+			lagging: Arc::default(),
+		};
+
+		(outbound, receiver)
+	}
+
+	/// Queues `frame`, waiting for room in the queue.
+	pub(crate) async fn send(&self, frame: ServerFrames) -> Result<(), ServerFrames> {
+		self.sender.send(frame).await.map_err(|e| e.0)
+	}
+
+	/// Queues `frame` if there is room. A full queue means the client is too
+	/// far behind, [`Outbound::lagging`] then resolves.
+	pub(crate) fn try_send(&self, frame: ServerFrames) -> Result<(), ServerFrames> {
+		self.sender.try_send(frame).map_err(|e| match e {
+			TrySendError::Full(frame) => {
+				self.lagging.notify_one();
+				frame
+			}
+			TrySendError::Closed(frame) => frame,
+		})
+	}
+
+	/// Resolves once a [`Outbound::try_send`] found the queue full.
+	pub(crate) async fn lagging(&self) {
+		self.lagging.notified().await
+	}
+}
+
 #[derive(Debug)]
 pub struct Connection {
 	pub address: SocketAddr,
 	buffer: BytesMut,
 	pub(crate) stream: BufWriter<TcpStream>,
+	metrics: Metrics,
+	timeouts: Timeouts,
+	/// When the last frame was complete, or the connection was opened.
+	last_frame: Instant,
+	/// When the first byte of the frame in `buffer` arrived.
+	frame_started: Option<Instant>,
 }
 
 impl Connection {
-	pub fn new(address: SocketAddr, socket: TcpStream) -> Connection {
+	pub(crate) fn new(
+		address: SocketAddr,
+		socket: TcpStream,
+		metrics: Metrics,
+		timeouts: Timeouts,
+	) -> Connection {
 		Connection {
 			address,
 			buffer: BytesMut::with_capacity(4 * 1024),
 			stream: BufWriter::new(socket),
+			metrics,
+			timeouts,
+			last_frame: Instant::now(),
+			frame_started: None,
 		}
 	}
 
 	pub fn get_address(&self) -> SocketAddr {
-		self.address.clone()
+		self.address
 	}
 
+	/// Reads the next frame. The deadlines for the idle and partial frame
+	/// timeouts are kept across calls, so a `select!` that drops the future
+	/// does not extend them.
 	pub async fn read_frame(&mut self) -> crate::Result<Option<ClientFrames>> {
 		loop {
 			if let Some(frame) = self.parse_frame()? {
+				self.last_frame = Instant::now();
+				self.frame_started = (!self.buffer.is_empty()).then_some(self.last_frame);
 				return Ok(Some(frame));
 			}
 
-			if 0 == self.stream.read_buf(&mut self.buffer).await? {
+			let deadline = match self.frame_started {
+				Some(started) => Some((started + self.timeouts.frame, SlowClient::PartialFrame)),
+				None => self
+					.timeouts
+					.idle
+					.map(|idle| (self.last_frame + idle, SlowClient::Idle)),
+			};
+
+			let read = self.stream.read_buf(&mut self.buffer);
+			let n = match deadline {
+				Some((deadline, timeout)) => time::timeout_at(deadline, read)
+					.await
+					.map_err(|_| timeout)??,
+				None => read.await?,
+			};
+			self.metrics.bytes_received.inc_by(n as u64);
+
+			if 0 == n {
 				if self.buffer.is_empty() {
 					return Ok(None);
 				} else {
-					return Err("connection reset by peer".into());
+					self.metrics.parse_errors.inc();
+					return Err(FrameError::Truncated.into());
 				}
 			}
+
+			self.frame_started.get_or_insert_with(Instant::now);
 		}
 	}
 
 	fn parse_frame(&mut self) -> crate::Result<Option<ClientFrames>> {
-		use frame::Error::Incomplete;
-
-		let mut buf = Cursor::new(&self.buffer[..]);
-
-		match ClientFrames::check(&mut buf) {
-			Ok(_) => {
-				let len = buf.position() as usize;
-				buf.set_position(0);
-
-				let frame = ClientFrames::parse(&mut buf)?;
-				self.buffer.advance(len);
-
-				Ok(Some(frame))
-			}
-			Err(Incomplete) => Ok(None),
-			Err(e) => Err(e.into()),
-		}
+		frame::parse_frame(&mut self.buffer).map_err(|e| {
+			self.metrics.parse_errors.inc();
+			e.into()
+		})
 	}
 
-	pub async fn write_frame(&mut self, frame: ServerFrames) -> tokio::io::Result<()> {
-		let _ = self.stream.write_all(&frame.convert_to_bytes()).await;
-		self.stream.flush().await?;
+	pub async fn write_frame(&mut self, frame: ServerFrames) -> crate::Result<()> {
+		let bytes = frame.to_bytes()?;
+		time::timeout(self.timeouts.write, async {
+			self.stream.write_all(&bytes).await?;
+			self.stream.flush().await
+		})
+		.await
+		.map_err(|_| SlowClient::Write)??;
+		self.metrics.bytes_sent.inc_by(bytes.len() as u64);
 		Ok(())
 	}
 }
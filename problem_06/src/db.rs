@@ -1,19 +1,30 @@
 use std::{
-	collections::{HashMap, HashSet},
+	cmp::Reverse,
+	collections::{BTreeMap, HashMap, HashSet},
+	io,
 	net::SocketAddr,
+	ops::RangeInclusive,
+	path::Path,
+	sync::{Arc, Mutex, MutexGuard},
 };
 
-use tokio::sync::mpsc;
-use tracing::info;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info, warn};
 
-use crate::frame::ServerFrames;
+use crate::{
+	audit::{AuditLog, AuditRecord},
+	connection::Outbound,
+	frame::ServerFrames,
+	journal::{self, Command, Entry, Journal},
+	observations::{CameraId, Neighbours, Observation, Timeline},
+	policy::SECONDS_PER_DAY,
+	Dispatch,
+};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub(crate) struct DispatcherId(pub(crate) SocketAddr);
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
-pub(crate) struct CameraId(pub(crate) SocketAddr);
-
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub(crate) struct Plate {
 	pub(crate) plate: PlateName,
@@ -30,7 +41,7 @@ pub(crate) struct Camera {
 	pub(crate) limit: Limit,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Deserialize, Serialize)]
 pub(crate) struct Ticket {
 	pub(crate) plate: String,
 	pub(crate) road: u16,
@@ -41,6 +52,33 @@ pub(crate) struct Ticket {
 	pub(crate) speed: u16,
 }
 
+impl TryFrom<ServerFrames> for Ticket {
+	type Error = ServerFrames;
+
+	fn try_from(frame: ServerFrames) -> Result<Self, Self::Error> {
+		match frame {
+			ServerFrames::Ticket {
+				plate,
+				road,
+				mile1,
+				timestamp1,
+				mile2,
+				timestamp2,
+				speed,
+			} => Ok(Ticket {
+				plate,
+				road,
+				mile1,
+				timestamp1,
+				mile2,
+				timestamp2,
+				speed,
+			}),
+			frame => Err(frame),
+		}
+	}
+}
+
 impl From<Ticket> for ServerFrames {
 	fn from(ticket: Ticket) -> Self {
 		ServerFrames::Ticket {
@@ -67,120 +105,540 @@ pub(crate) struct Timestamp(pub(crate) u32);
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub(crate) struct Mile(pub(crate) u16);
 
+type Dispatcher = (DispatcherId, Outbound);
+
+/// How many observations and ticketed days the state holds, or dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Stats {
+	pub(crate) observations: usize,
+	pub(crate) ticketed_days: usize,
+}
+
+/// Everything about one road, observations on different roads are handled
+/// without waiting for each other.
+#[derive(Debug, Default)]
+struct RoadShard {
+	cameras: HashMap<CameraId, Camera>,
+	dispatchers: Vec<Dispatcher>,
+	next_dispatcher: usize,
+	observations: HashMap<PlateName, Timeline>,
+	/// Observations before this timestamp were dropped by the last
+	/// compaction, older ones are ignored from then on.
+	cutoff: u32,
+	open_tickets: Vec<Ticket>,
+}
+
+/// The connections and open tickets of a road, for inspection.
+#[derive(Debug)]
+pub(crate) struct RoadSnapshot {
+	pub(crate) road: Road,
+	pub(crate) cameras: Vec<(CameraId, Camera)>,
+	pub(crate) dispatchers: Vec<DispatcherId>,
+	pub(crate) open_tickets: Vec<Ticket>,
+}
+
+/// Days on which a plate got a ticket, across all roads.
+#[derive(Debug, Default)]
+struct Ledger {
+	days: HashSet<(Timestamp, String)>,
+	/// The days before this one were dropped by a compaction, no ticket can
+	/// fall on them anymore.
+	first_day: u32,
+	/// Tickets issued since the start by the day of their first observation.
+	issued: BTreeMap<u32, usize>,
+}
+
+/// The state shared by all connections.
+///
+/// Every lock is held only for a few map operations and never across an
+/// await point. Locks are taken in the order roads, shard, ledger, audit log.
+/// Journal entries are handed to a writer task while the lock guarding the
+/// change is held, so they are written in the order the changes were made.
 #[derive(Debug)]
 pub(crate) struct Db {
-	cameras: HashMap<CameraId, Camera>,
-	dispatchers: HashMap<Road, Vec<(DispatcherId, mpsc::Sender<ServerFrames>)>>,
-	plates: HashMap<(PlateName, Road), Vec<(Mile, Timestamp)>>,
-	ticketed_plates_by_day: HashSet<(Timestamp, String)>,
-	open_tickets: HashMap<Road, Vec<Ticket>>,
+	roads: Mutex<HashMap<Road, Arc<Mutex<RoadShard>>>>,
+	ticketed_plates_by_day: Mutex<Ledger>,
+	journal: Option<mpsc::UnboundedSender<Command>>,
+	audit_log: Option<Mutex<AuditLog>>,
+	dispatch: Dispatch,
 }
 
 impl Db {
-	pub(crate) fn new() -> Db {
+	pub(crate) fn new(dispatch: Dispatch) -> Db {
+		Db {
+			roads: Mutex::default(),
+			ticketed_plates_by_day: Mutex::default(),
+			journal: None,
+			audit_log: None,
+			dispatch,
+		}
+	}
+
+	/// Restores the state recorded in the journal at `path` and keeps
+	/// recording every change to it from a task spawned on the current
+	/// runtime.
+	pub(crate) fn open(path: &Path, dispatch: Dispatch) -> io::Result<Db> {
+		let (journal, entries) = Journal::open(path)?;
+		info!("Replaying {} journal entries from {path:?}", entries.len());
+
+		let mut db = Db::new(dispatch);
+		for entry in entries {
+			db.apply(entry);
+		}
+		let (commands, receive_commands) = mpsc::unbounded_channel();
+		tokio::spawn(journal::write(journal, receive_commands));
+		db.journal = Some(commands);
+
+		Ok(db)
+	}
+
+	/// Appends an [`AuditRecord`] for every ticket event to `audit_log`.
+	pub(crate) fn with_audit_log(self, audit_log: AuditLog) -> Db {
 		Db {
-			cameras: HashMap::new(),
-			dispatchers: HashMap::new(),
-			plates: HashMap::new(),
-			ticketed_plates_by_day: HashSet::new(),
-			open_tickets: HashMap::new(),
+			audit_log: Some(Mutex::new(audit_log)),
+			..self
 		}
 	}
 
-	pub(crate) fn get_camera(&self, camera_id: CameraId) -> Option<Camera> {
-		self.cameras.get(&camera_id).cloned()
+	fn shard(&self, road: &Road) -> Arc<Mutex<RoadShard>> {
+		lock(&self.roads).entry(road.clone()).or_default().clone()
+	}
+
+	/// Queues `entry` for the journal, if there is one.
+	fn record(&self, entry: Entry) {
+		self.journal_command(Command::Append(entry));
 	}
 
-	pub(crate) fn add_camera(&mut self, camera_id: CameraId, camera: Camera) {
-		self.cameras.insert(camera_id, camera);
+	fn journal_command(&self, command: Command) {
+		if let Some(journal) = &self.journal {
+			if journal.send(command).is_err() {
+				error!("The journal writer stopped, the change is not recorded");
+			}
+		}
 	}
 
+	/// Waits until everything recorded so far is written to the journal.
+	pub(crate) async fn flush_journal(&self) {
+		let (done, flushed) = oneshot::channel();
+		self.journal_command(Command::Flush(done));
+		if self.journal.is_some() {
+			let _ = flushed.await;
+		}
+	}
+
+	/// Writes `record` to the audit log, if there is one.
+	pub(crate) fn audit(&self, record: AuditRecord) {
+		if let Some(audit_log) = &self.audit_log {
+			if let Err(e) = lock(audit_log).append(&record) {
+				error!("Could not write to audit log: {e}");
+			}
+		}
+	}
+
+	fn apply(&self, entry: Entry) {
+		match entry {
+			Entry::Observation {
+				plate,
+				road,
+				mile,
+				timestamp,
+				camera,
+			} => {
+				let shard = self.shard(&Road(road));
+				let mut shard = lock(&shard);
+				if timestamp < shard.cutoff {
+					return;
+				}
+				shard
+					.observations
+					.entry(PlateName(plate))
+					.or_default()
+					.insert(mile, timestamp, camera.map(CameraId));
+			}
+			Entry::TicketedDay { plate, day } => {
+				let mut ledger = lock(&self.ticketed_plates_by_day);
+				if day >= ledger.first_day {
+					ledger.days.insert((Timestamp(day), plate));
+				}
+			}
+			Entry::OpenTicket(ticket) => {
+				lock(&self.shard(&Road(ticket.road)))
+					.open_tickets
+					.push(ticket);
+			}
+			Entry::TicketDelivered(ticket) | Entry::TicketDropped(ticket) => {
+				let shard = self.shard(&Road(ticket.road));
+				let mut shard = lock(&shard);
+				if let Some(position) = shard.open_tickets.iter().position(|t| *t == ticket) {
+					shard.open_tickets.remove(position);
+				}
+			}
+			Entry::Cutoff { road, timestamp } => {
+				let shard = self.shard(&Road(road));
+				let mut shard = lock(&shard);
+				shard.cutoff = shard.cutoff.max(timestamp);
+				for timeline in shard.observations.values_mut() {
+					timeline.retain_since(timestamp);
+				}
+				shard
+					.observations
+					.retain(|_, timeline| !timeline.is_empty());
+			}
+			Entry::FirstDay { day } => {
+				let mut ledger = lock(&self.ticketed_plates_by_day);
+				ledger.first_day = ledger.first_day.max(day);
+				ledger.days.retain(|(ticketed, _)| ticketed.0 >= day);
+			}
+		}
+	}
+
+	/// Registers the camera and returns the limit other cameras on its road
+	/// reported, if it differs from its own.
+	pub(crate) fn add_camera(&self, camera_id: CameraId, camera: Camera) -> Option<Limit> {
+		let shard = self.shard(&camera.road);
+		let mut shard = lock(&shard);
+
+		let conflicting = shard
+			.cameras
+			.values()
+			.find(|other| other.limit != camera.limit)
+			.map(|other| other.limit.clone());
+		shard.cameras.insert(camera_id, camera);
+
+		conflicting
+	}
+
+	pub(crate) fn remove_camera(&self, camera_id: &CameraId, road: &Road) {
+		lock(&self.shard(road)).cameras.remove(camera_id);
+	}
+
+	/// Registers the dispatcher for `roads` and returns the tickets that
+	/// waited for a dispatcher on one of them. The journal keeps them open
+	/// until a dispatcher wrote them to its client.
 	pub(crate) fn add_dispatcher(
-		&mut self,
+		&self,
 		dispatcher_id: DispatcherId,
 		roads: Vec<u16>,
-		writer_stream: mpsc::Sender<ServerFrames>,
-	) {
+		outbound: Outbound,
+	) -> Vec<Ticket> {
 		info!("Adding new dispatcher for roads: {roads:?}");
-		for r in roads.iter() {
-			self.dispatchers
-				.entry(Road(*r))
-				.or_insert(Vec::new())
-				.push((dispatcher_id.clone(), writer_stream.clone()));
+		let mut waiting = Vec::new();
+
+		for road in roads {
+			let shard = self.shard(&Road(road));
+			let mut shard = lock(&shard);
+			shard
+				.dispatchers
+				.push((dispatcher_id.clone(), outbound.clone()));
+
+			waiting.append(&mut shard.open_tickets);
 		}
+
+		waiting
 	}
 
-	pub(crate) fn get_dispatcher_for_road(&self, road: Road) -> Option<mpsc::Sender<ServerFrames>> {
-		let senders = self.dispatchers.get(&road);
-		if senders.is_none() {
-			return None;
+	pub(crate) fn remove_dispatcher(&self, dispatcher_id: &DispatcherId) {
+		info!("Removing dispatcher: {dispatcher_id:?}");
+		for shard in lock(&self.roads).values() {
+			lock(shard)
+				.dispatchers
+				.retain(|(id, _)| id != dispatcher_id);
 		}
+	}
 
-		senders.unwrap().first().map(|(_, s)| s.clone())
+	/// Records the newly issued `ticket` as open until
+	/// [`Db::ticket_delivered`] is called for it, so it survives a restart
+	/// while it waits for a dispatcher or in a dispatcher's queue.
+	pub(crate) fn open_ticket(&self, ticket: &Ticket) {
+		self.record(Entry::OpenTicket(ticket.clone()));
 	}
 
-	pub(crate) fn add_open_ticket(&mut self, ticket: Ticket) {
-		info!("Adding open ticket: {ticket:?}");
-		self.open_tickets
-			.entry(Road(ticket.road))
-			.or_insert(Vec::new())
-			.push(ticket);
+	/// Records that a dispatcher wrote `ticket` to its client.
+	pub(crate) fn ticket_delivered(&self, ticket: &Ticket) {
+		self.record(Entry::TicketDelivered(ticket.clone()));
 	}
 
-	pub(crate) fn get_open_tickets(&self) -> Vec<Ticket> {
-		self.open_tickets.values().flatten().cloned().collect()
+	/// Returns the dispatchers for the road of `ticket` in the order they
+	/// should be tried. Without any, the ticket is kept as open ticket in the
+	/// same step, so a dispatcher that registers concurrently picks it up.
+	pub(crate) fn dispatchers_or_keep(&self, ticket: &Ticket) -> Vec<Dispatcher> {
+		let shard = self.shard(&Road(ticket.road));
+		let mut shard = lock(&shard);
+
+		if shard.dispatchers.is_empty() {
+			info!("Adding open ticket: {ticket:?}");
+			shard.open_tickets.push(ticket.clone());
+			return Vec::new();
+		}
+
+		let mut dispatchers = shard.dispatchers.clone();
+
+		if self.dispatch == Dispatch::RoundRobin {
+			let start = shard.next_dispatcher % dispatchers.len();
+			dispatchers.rotate_left(start);
+			shard.next_dispatcher = shard.next_dispatcher.wrapping_add(1);
+		}
+
+		dispatchers
 	}
 
-	pub(crate) fn remove_open_ticket(&mut self, road: Road, ticket: Ticket) -> bool {
-		info!("Removing open ticket: {ticket:?}");
-		if let Some(tickets) = self.open_tickets.get_mut(&road) {
-			tickets.retain(|t| t.plate != ticket.plate);
-			if tickets.is_empty() {
-				self.open_tickets.remove(&road);
+	/// Records that `camera` on the connection `camera_id` saw `plate` and returns the observations of the
+	/// plate on the same road right before and after it. Returns `None`
+	/// without recording anything if the plate was already seen on the road
+	/// at that time, or the observation is older than the retention.
+	pub(crate) fn add_observation(
+		&self,
+		camera_id: CameraId,
+		camera: &Camera,
+		plate: &Plate,
+	) -> Option<Neighbours> {
+		let shard = self.shard(&camera.road);
+		let mut shard = lock(&shard);
+
+		if plate.timestamp.0 < shard.cutoff {
+			debug!("Observation of {plate:?} by {camera:?} is older than the retention");
+			return None;
+		}
+
+		let timeline = shard.observations.entry(plate.plate.clone()).or_default();
+		match timeline.mile_at(plate.timestamp.0) {
+			Some(mile) if mile == camera.mile.0 => {
+				debug!("Duplicate observation of {plate:?} by {camera:?}");
+				return None;
+			}
+			Some(mile) => {
+				warn!("{plate:?} seen by {camera:?} was at mile {mile} at the same time");
+				return None;
 			}
-			return true;
+			None => {}
 		}
-		false
+
+		self.record(Entry::Observation {
+			plate: plate.plate.0.clone(),
+			road: camera.road.0,
+			mile: camera.mile.0,
+			timestamp: plate.timestamp.0,
+			camera: Some(camera_id.0),
+		});
+
+		Some(timeline.insert(camera.mile.0, plate.timestamp.0, Some(camera_id)))
 	}
 
-	pub(crate) fn get_plates_by_road(
+	/// The camera that saw `plate_name` on `road` at `timestamp`, if it is
+	/// known.
+	pub(crate) fn camera_at(
 		&self,
-		plate: Plate,
-		road: Road,
-	) -> Option<Vec<(Mile, Timestamp)>> {
-		self.plates.get(&(plate.plate, road)).cloned()
-	}
-
-	pub(crate) fn add_plate(&mut self, camera_id: CameraId, plate: Plate) {
-		//TODO: Check if the same plate was already added for the road AND MILE
-		let camera = self.get_camera(camera_id).unwrap();
-
-		match self
-			.plates
-			.get_mut(&(plate.plate.clone(), camera.road.clone()))
-		{
-			Some(v) => v.push((camera.mile, plate.timestamp)),
-			None => {
-				self.plates.insert(
-					(plate.clone().plate, camera.road),
-					vec![(camera.mile, plate.timestamp)],
+		road: &Road,
+		plate_name: &PlateName,
+		timestamp: u32,
+	) -> Option<CameraId> {
+		lock(&self.shard(road))
+			.observations
+			.get(plate_name)?
+			.camera_at(timestamp)
+	}
+
+	/// Every road the state knows of, ordered by road.
+	pub(crate) fn snapshot(&self) -> Vec<RoadSnapshot> {
+		let mut roads: Vec<_> = lock(&self.roads)
+			.iter()
+			.map(|(road, shard)| (road.clone(), shard.clone()))
+			.collect();
+		roads.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+		roads
+			.into_iter()
+			.map(|(road, shard)| {
+				let shard = lock(&shard);
+				let mut cameras: Vec<_> = shard
+					.cameras
+					.iter()
+					.map(|(id, camera)| (*id, camera.clone()))
+					.collect();
+				cameras.sort();
+
+				RoadSnapshot {
+					road,
+					cameras,
+					dispatchers: shard.dispatchers.iter().map(|(id, _)| id.clone()).collect(),
+					open_tickets: shard.open_tickets.clone(),
+				}
+			})
+			.collect()
+	}
+
+	/// The observations of `plate` on every road, the latest first.
+	pub(crate) fn observations(&self, plate_name: &PlateName) -> Vec<(Road, Observation)> {
+		let shards: Vec<_> = lock(&self.roads)
+			.iter()
+			.map(|(road, shard)| (road.clone(), shard.clone()))
+			.collect();
+
+		let mut observations = Vec::new();
+		for (road, shard) in shards {
+			if let Some(timeline) = lock(&shard).observations.get(plate_name) {
+				observations.extend(
+					timeline
+						.iter()
+						.map(|observation| (road.clone(), observation)),
 				);
 			}
 		}
+		observations.sort_by_key(|(_, observation)| Reverse(observation.timestamp));
+
+		observations
+	}
+
+	/// How many tickets were issued since the start for each day, counted on
+	/// the day of their first observation only.
+	pub(crate) fn tickets_per_day(&self) -> BTreeMap<u32, usize> {
+		lock(&self.ticketed_plates_by_day).issued.clone()
+	}
+
+	/// Removes the open ticket of `plate` on `road` that starts at
+	/// `timestamp1`, to hand it to a dispatcher again. The journal keeps it
+	/// open until it is delivered.
+	pub(crate) fn take_open_ticket(
+		&self,
+		road: &Road,
+		plate: &str,
+		timestamp1: u32,
+	) -> Option<Ticket> {
+		self.remove_open_ticket(road, plate, timestamp1, None)
+	}
+
+	/// Removes the open ticket of `plate` on `road` that starts at
+	/// `timestamp1` for good.
+	pub(crate) fn drop_open_ticket(
+		&self,
+		road: &Road,
+		plate: &str,
+		timestamp1: u32,
+	) -> Option<Ticket> {
+		self.remove_open_ticket(road, plate, timestamp1, Some(Entry::TicketDropped))
+	}
+
+	fn remove_open_ticket(
+		&self,
+		road: &Road,
+		plate: &str,
+		timestamp1: u32,
+		entry: Option<fn(Ticket) -> Entry>,
+	) -> Option<Ticket> {
+		let shard = self.shard(road);
+		let mut shard = lock(&shard);
+
+		let position = shard
+			.open_tickets
+			.iter()
+			.position(|ticket| ticket.plate == plate && ticket.timestamp1 == timestamp1)?;
+		let ticket = shard.open_tickets.remove(position);
+		if let Some(entry) = entry {
+			self.record(entry(ticket.clone()));
+		}
+
+		Some(ticket)
 	}
 
-	pub(crate) fn ticket_plate(&mut self, day: u32, plate_name: PlateName) {
-		info!("Add {plate_name:?} for day:{day} ");
-		self.ticketed_plates_by_day
-			.insert((Timestamp(day), plate_name.0));
+	/// Drops the observations older than `retention_days` before the latest
+	/// observation on their road, and the ticketed days no kept observation
+	/// can fall on anymore. Older observations are ignored from then on, so
+	/// a car cannot be ticketed again for a dropped day. Returns what is held
+	/// afterwards and what was dropped. Without a retention nothing is
+	/// dropped.
+	///
+	/// The journal is rewritten to match, without what was dropped and the
+	/// tickets that were delivered since.
+	pub(crate) fn compact(&self, retention_days: Option<u32>) -> (Stats, Stats) {
+		let mut held = Stats::default();
+		let mut evicted = Stats::default();
+		let retention = retention_days.map(|days| days.saturating_mul(SECONDS_PER_DAY));
+
+		let shards: Vec<_> = lock(&self.roads)
+			.iter()
+			.map(|(road, shard)| (road.clone(), shard.clone()))
+			.collect();
+		let mut oldest_kept: Option<u32> = None;
+
+		for (road, shard) in shards {
+			let mut shard = lock(&shard);
+			let Some(latest) = shard
+				.observations
+				.values()
+				.filter_map(Timeline::latest)
+				.max()
+			else {
+				continue;
+			};
+			let cutoff = retention.map_or(0, |retention| latest.saturating_sub(retention));
+			oldest_kept = Some(oldest_kept.map_or(cutoff, |oldest| oldest.min(cutoff)));
+			if cutoff > shard.cutoff {
+				self.record(Entry::Cutoff {
+					road: road.0,
+					timestamp: cutoff,
+				});
+				shard.cutoff = cutoff;
+			}
+
+			shard.observations.retain(|_, timeline| {
+				evicted.observations += timeline.retain_since(cutoff);
+				held.observations += timeline.len();
+				!timeline.is_empty()
+			});
+		}
+
+		let mut ledger = lock(&self.ticketed_plates_by_day);
+		if let (Some(_), Some(oldest_kept)) = (retention, oldest_kept) {
+			// Roads without a cutoff yet may still see observations on the
+			// dropped days, tickets for them are refused instead.
+			let first_day = oldest_kept / SECONDS_PER_DAY;
+			if first_day > ledger.first_day {
+				self.record(Entry::FirstDay { day: first_day });
+				ledger.first_day = first_day;
+			}
+			let first_day = ledger.first_day;
+			let before = ledger.days.len();
+			ledger.days.retain(|(day, _)| day.0 >= first_day);
+			evicted.ticketed_days = before - ledger.days.len();
+		}
+		held.ticketed_days = ledger.days.len();
+		drop(ledger);
+
+		self.journal_command(Command::Compact);
+
+		(held, evicted)
 	}
 
-	pub(crate) fn is_plate_ticketed_for_day(&self, day: u32, plate_name: PlateName) -> bool {
-		info!(
-			"Current ticketed plates, by day: {:?}",
-			self.ticketed_plates_by_day
-		);
-		self.ticketed_plates_by_day
-			.contains(&(Timestamp(day), plate_name.0))
+	/// Marks every day in `days` as ticketed for `plate`, unless one of them
+	/// already is or was dropped by a compaction.
+	pub(crate) fn ticket_plate(&self, days: RangeInclusive<u32>, plate_name: &PlateName) -> bool {
+		let mut ledger = lock(&self.ticketed_plates_by_day);
+
+		if *days.start() < ledger.first_day
+			|| days.clone().any(|day| {
+				ledger
+					.days
+					.contains(&(Timestamp(day), plate_name.0.clone()))
+			}) {
+			return false;
+		}
+
+		*ledger.issued.entry(*days.start()).or_default() += 1;
+		for day in days {
+			info!("Add {plate_name:?} for day:{day} ");
+			self.record(Entry::TicketedDay {
+				plate: plate_name.0.clone(),
+				day,
+			});
+			ledger.days.insert((Timestamp(day), plate_name.0.clone()));
+		}
+
+		true
 	}
 }
+
+/// Locks `mutex`, a panic while holding one of the locks leaves consistent
+/// enough state to carry on.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -1,8 +1,8 @@
-use std::{fmt, io::Cursor, num::TryFromIntError, string::FromUtf8Error};
+use std::{fmt, io::Cursor};
 
 use bytes::{Buf, BufMut, BytesMut};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ClientFrames {
 	Plate { plate: String, timestamp: u32 },
 	WantHeartbeat { interval: u32 },
@@ -10,7 +10,7 @@ pub enum ClientFrames {
 	IAmDispatcher { roads: Vec<u16> },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ServerFrames {
 	Error {
 		msg: String,
@@ -27,20 +27,46 @@ pub enum ServerFrames {
 	Heartbeat,
 }
 
-#[derive(Debug)]
-pub enum Error {
-	Incomplete,
-	Other(crate::Error),
+/// Why bytes could not be read as a frame, or a frame not be written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+	/// The type byte does not belong to any frame in this direction.
+	UnknownType(u8),
+	/// The bytes end before the frame does. While reading from a stream this
+	/// only means more bytes are needed.
+	Truncated,
+	/// A string field is not valid UTF-8.
+	InvalidUtf8,
+	/// A string or road list of this length does not fit its one byte length
+	/// prefix.
+	TooLong(usize),
 }
 
-impl ClientFrames {
-	pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+/// Writes a frame in its wire format.
+pub trait Encode {
+	/// Appends the frame to `dst`, which is left untouched on error.
+	fn encode(&self, dst: &mut BytesMut) -> Result<(), FrameError>;
+
+	fn to_bytes(&self) -> Result<BytesMut, FrameError> {
+		let mut buf = BytesMut::new();
+		self.encode(&mut buf)?;
+		Ok(buf)
+	}
+}
+
+/// Reads a frame from its wire format.
+pub trait Decode: Sized {
+	/// Advances `src` to the end of the next frame if it is complete, without
+	/// allocating anything.
+	fn check(src: &mut Cursor<&[u8]>) -> Result<(), FrameError>;
+
+	/// Reads the next frame, `check` must have succeeded on the same bytes.
+	fn parse(src: &mut Cursor<&[u8]>) -> Result<Self, FrameError>;
+}
+
+impl Decode for ClientFrames {
+	fn check(src: &mut Cursor<&[u8]>) -> Result<(), FrameError> {
 		match get_u8(src)? {
-			// Error: msg: str (Server -> Client)
-			// 0x10 => {
-			//     let n = get_length(src)?;
-			//     skip(src, n as usize)
-			// }
 			// Plate: plate: str, timestamp: u32
 			0x20 => {
 				// Read length character of the plate string
@@ -51,19 +77,11 @@ impl ClientFrames {
 				get_u32(src)?;
 				Ok(())
 			}
-			// Ticket (just Server -> Client)
-			// 0x21 => {
-			//     Ok(())
-			// }
 			// Want Heartbeat: interval: u32
 			0x40 => {
 				get_u32(src)?;
 				Ok(())
 			}
-			// Heartbeat (just Server -> Client)
-			// 0x41 => {
-			//     Ok(())
-			// }
 			// IAmCamera: road: u16, mile: u16, limit: u16
 			0x80 => {
 				// road
@@ -77,23 +95,17 @@ impl ClientFrames {
 			// IAmDispatcher: numroads: u8, roads: [u16]
 			0x81 => {
 				// numroads
-				let amount = get_u8(src)? * 2;
+				let amount = get_u8(src)? as usize * 2;
 				// roads
-				skip(src, amount as usize)?;
+				skip(src, amount)?;
 				Ok(())
 			}
-			actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+			actual => Err(FrameError::UnknownType(actual)),
 		}
 	}
 
-	pub fn parse(src: &mut Cursor<&[u8]>) -> Result<ClientFrames, Error> {
+	fn parse(src: &mut Cursor<&[u8]>) -> Result<ClientFrames, FrameError> {
 		match get_u8(src)? {
-			// Error: msg: str (Server -> Client)
-			// 0x10 => {
-			//     let n = get_length(src)?;
-			//     let msg = get_str(src, n)?.to_string();
-			//     Ok(Frame::Error { msg })
-			// }
 			// Plate: plate: str, timestamp: u32
 			0x20 => {
 				// Read length character of the plate string
@@ -104,19 +116,11 @@ impl ClientFrames {
 				let timestamp = get_u32(src)?;
 				Ok(ClientFrames::Plate { plate, timestamp })
 			}
-			// Ticket (just Server -> Client)
-			// 0x21 => {
-			//     Ok(())
-			// }
 			// Want Heartbeat: interval: u32
 			0x40 => {
 				let interval = get_u32(src)?;
 				Ok(ClientFrames::WantHeartbeat { interval })
 			}
-			// Heartbeat (just Server -> Client)
-			// 0x41 => {
-			//     Ok(())
-			// }
 			// IAmCamera: road: u16, mile: u16, limit: u16
 			0x80 => {
 				// road
@@ -136,22 +140,103 @@ impl ClientFrames {
 
 				Ok(ClientFrames::IAmDispatcher { roads })
 			}
-			actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+			actual => Err(FrameError::UnknownType(actual)),
 		}
 	}
 }
 
-impl ServerFrames {
-	pub(crate) fn convert_to_bytes(&self) -> BytesMut {
+impl Encode for ClientFrames {
+	fn encode(&self, dst: &mut BytesMut) -> Result<(), FrameError> {
 		match self {
-			ServerFrames::Error { msg } => {
-				let mut buf = BytesMut::with_capacity(1 + 1 + msg.len());
+			ClientFrames::Plate { plate, timestamp } => {
+				let len = str_len(plate)?;
+				dst.put_u8(0x20);
+				put_str(dst, len, plate);
+				dst.put_u32(*timestamp);
+			}
+			ClientFrames::WantHeartbeat { interval } => {
+				dst.put_u8(0x40);
+				dst.put_u32(*interval);
+			}
+			ClientFrames::IAmCamera { road, mile, limit } => {
+				dst.put_u8(0x80);
+				dst.put_u16(*road);
+				dst.put_u16(*mile);
+				dst.put_u16(*limit);
+			}
+			ClientFrames::IAmDispatcher { roads } => {
+				let len =
+					u8::try_from(roads.len()).map_err(|_| FrameError::TooLong(roads.len()))?;
+				dst.put_u8(0x81);
+				dst.put_u8(len);
+				for road in roads {
+					dst.put_u16(*road);
+				}
+			}
+		}
 
-				buf.put_u8(0x10);
-				buf.put_u8(msg.len() as u8);
-				buf.put_slice(msg.as_bytes());
+		Ok(())
+	}
+}
 
-				return buf;
+impl Decode for ServerFrames {
+	fn check(src: &mut Cursor<&[u8]>) -> Result<(), FrameError> {
+		match get_u8(src)? {
+			// Error: msg: str
+			0x10 => {
+				let n = get_length(src)?;
+				skip(src, n)
+			}
+			// Ticket: plate: str, road: u16, mile1: u16, timestamp1: u32,
+			// mile2: u16, timestamp2: u32, speed: u16
+			0x21 => {
+				let n = get_length(src)?;
+				skip(src, n)?;
+				skip(src, 2 + 2 + 4 + 2 + 4 + 2)
+			}
+			// Heartbeat
+			0x41 => Ok(()),
+			actual => Err(FrameError::UnknownType(actual)),
+		}
+	}
+
+	fn parse(src: &mut Cursor<&[u8]>) -> Result<ServerFrames, FrameError> {
+		match get_u8(src)? {
+			// Error: msg: str
+			0x10 => {
+				let n = get_length(src)?;
+				let msg = get_str(src, n)?.to_string();
+				Ok(ServerFrames::Error { msg })
+			}
+			// Ticket: plate: str, road: u16, mile1: u16, timestamp1: u32,
+			// mile2: u16, timestamp2: u32, speed: u16
+			0x21 => {
+				let n = get_length(src)?;
+				let plate = get_str(src, n)?.to_string();
+				Ok(ServerFrames::Ticket {
+					plate,
+					road: get_u16(src)?,
+					mile1: get_u16(src)?,
+					timestamp1: get_u32(src)?,
+					mile2: get_u16(src)?,
+					timestamp2: get_u32(src)?,
+					speed: get_u16(src)?,
+				})
+			}
+			// Heartbeat
+			0x41 => Ok(ServerFrames::Heartbeat),
+			actual => Err(FrameError::UnknownType(actual)),
+		}
+	}
+}
+
+impl Encode for ServerFrames {
+	fn encode(&self, dst: &mut BytesMut) -> Result<(), FrameError> {
+		match self {
+			ServerFrames::Error { msg } => {
+				let len = str_len(msg)?;
+				dst.put_u8(0x10);
+				put_str(dst, len, msg);
 			}
 			ServerFrames::Ticket {
 				plate,
@@ -162,135 +247,123 @@ impl ServerFrames {
 				timestamp2,
 				speed,
 			} => {
-				let mut buf = BytesMut::with_capacity(1 + 1 + plate.len() + 2 + 2 + 4 + 2 + 4 + 2);
-
-				buf.put_u8(0x21);
-				buf.put_u8(plate.len() as u8);
-				buf.put_slice(plate.as_bytes());
-				buf.put_u16(*road);
-				buf.put_u16(*mile1);
-				buf.put_u32(*timestamp1);
-				buf.put_u16(*mile2);
-				buf.put_u32(*timestamp2);
-				buf.put_u16(*speed);
-
-				return buf;
+				let len = str_len(plate)?;
+				dst.put_u8(0x21);
+				put_str(dst, len, plate);
+				dst.put_u16(*road);
+				dst.put_u16(*mile1);
+				dst.put_u32(*timestamp1);
+				dst.put_u16(*mile2);
+				dst.put_u32(*timestamp2);
+				dst.put_u16(*speed);
 			}
 			ServerFrames::Heartbeat => {
-				let mut buf = BytesMut::new();
-
-				buf.put_u8(0x41);
-
-				return buf;
+				dst.put_u8(0x41);
 			}
 		}
-	}
-}
 
-fn get_str<'a>(src: &mut Cursor<&'a [u8]>, len: usize) -> Result<&'a str, Error> {
-	if src.remaining() < len {
-		return Err(Error::Incomplete);
+		Ok(())
 	}
+}
 
-	let position = src.position() as usize;
-	let slice = &src.get_ref()[position..position + len];
-
-	let message =
-		std::str::from_utf8(slice).map_err(|_| "protocol error; invalid frame format".into());
+/// Parses the first frame of `buffer` and removes its bytes.
+///
+/// Returns `Ok(None)` and leaves `buffer` untouched if the frame is not
+/// complete yet.
+pub fn parse_frame<F: Decode>(buffer: &mut BytesMut) -> Result<Option<F>, FrameError> {
+	let mut buf = Cursor::new(&buffer[..]);
 
-	src.advance(len);
+	match F::check(&mut buf) {
+		Ok(_) => {
+			let len = buf.position() as usize;
+			buf.set_position(0);
 
-	message
-}
+			let frame = F::parse(&mut buf)?;
+			buffer.advance(len);
 
-fn get_u16_vec<'a>(src: &mut Cursor<&'a [u8]>, len: usize) -> Result<Vec<u16>, Error> {
-	if src.remaining() < len {
-		return Err(Error::Incomplete);
+			Ok(Some(frame))
+		}
+		Err(FrameError::Truncated) => Ok(None),
+		Err(e) => Err(e),
 	}
+}
 
-	let mut roads = Vec::new();
-
-	for _ in 0..len {
-		let road = src.get_u16();
-		roads.push(road);
-	}
+fn str_len(value: &str) -> Result<u8, FrameError> {
+	u8::try_from(value.len()).map_err(|_| FrameError::TooLong(value.len()))
+}
 
-	Ok(roads)
+fn put_str(dst: &mut BytesMut, len: u8, value: &str) {
+	dst.put_u8(len);
+	dst.put_slice(value.as_bytes());
 }
 
-fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
+/// Fails unless at least `n` more bytes are available, every read goes
+/// through here before touching the cursor.
+fn ensure(src: &Cursor<&[u8]>, n: usize) -> Result<(), FrameError> {
 	if src.remaining() < n {
-		return Err(Error::Incomplete);
+		return Err(FrameError::Truncated);
 	}
 
-	src.advance(n);
 	Ok(())
 }
 
-fn get_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
-	if !src.has_remaining() {
-		return Err(Error::Incomplete);
-	}
+fn get_str<'a>(src: &mut Cursor<&'a [u8]>, len: usize) -> Result<&'a str, FrameError> {
+	ensure(src, len)?;
 
-	Ok(src.get_u8())
-}
+	let position = src.position() as usize;
+	let slice = &src.get_ref()[position..position + len];
+	let message = std::str::from_utf8(slice).map_err(|_| FrameError::InvalidUtf8)?;
 
-fn get_u16(src: &mut Cursor<&[u8]>) -> Result<u16, Error> {
-	if !src.has_remaining() {
-		return Err(Error::Incomplete);
-	}
+	src.advance(len);
 
-	Ok(src.get_u16())
+	Ok(message)
 }
 
-fn get_u32(src: &mut Cursor<&[u8]>) -> Result<u32, Error> {
-	if !src.has_remaining() {
-		return Err(Error::Incomplete);
-	}
+fn get_u16_vec(src: &mut Cursor<&[u8]>, len: usize) -> Result<Vec<u16>, FrameError> {
+	ensure(src, len * 2)?;
 
-	Ok(src.get_u32())
+	Ok((0..len).map(|_| src.get_u16()).collect())
 }
 
-// Same as get_u8, but the current cursor points to the byte of the length of a message string.
-fn get_length(src: &mut Cursor<&[u8]>) -> Result<usize, Error> {
-	if !src.has_remaining() {
-		return Err(Error::Incomplete);
-	}
+fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), FrameError> {
+	ensure(src, n)?;
 
-	Ok(src.get_u8() as usize)
+	src.advance(n);
+	Ok(())
 }
 
-impl From<String> for Error {
-	fn from(src: String) -> Error {
-		Error::Other(src.into())
-	}
+fn get_u8(src: &mut Cursor<&[u8]>) -> Result<u8, FrameError> {
+	ensure(src, 1)?;
+
+	Ok(src.get_u8())
 }
 
-impl From<&str> for Error {
-	fn from(src: &str) -> Error {
-		src.to_string().into()
-	}
+fn get_u16(src: &mut Cursor<&[u8]>) -> Result<u16, FrameError> {
+	ensure(src, 2)?;
+
+	Ok(src.get_u16())
 }
 
-impl From<FromUtf8Error> for Error {
-	fn from(_src: FromUtf8Error) -> Error {
-		"protocol error; invalid frame format".into()
-	}
+fn get_u32(src: &mut Cursor<&[u8]>) -> Result<u32, FrameError> {
+	ensure(src, 4)?;
+
+	Ok(src.get_u32())
 }
 
-impl From<TryFromIntError> for Error {
-	fn from(_src: TryFromIntError) -> Error {
-		"protocol error; invalid frame format".into()
-	}
+// Same as get_u8, but the current cursor points to the byte of the length of a message string.
+fn get_length(src: &mut Cursor<&[u8]>) -> Result<usize, FrameError> {
+	Ok(get_u8(src)? as usize)
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for FrameError {}
 
-impl fmt::Display for Error {
+impl fmt::Display for FrameError {
 	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
 		match self {
-			Error::Incomplete => "stream ended early".fmt(fmt),
-			Error::Other(err) => err.fmt(fmt),
+			FrameError::UnknownType(ty) => write!(fmt, "unknown frame type 0x{ty:02x}"),
+			FrameError::Truncated => "frame ended early".fmt(fmt),
+			FrameError::InvalidUtf8 => "string is not valid UTF-8".fmt(fmt),
+			FrameError::TooLong(len) => write!(fmt, "length {len} does not fit in one byte"),
 		}
 	}
 }
@@ -1,45 +1,46 @@
 use std::time::Duration;
 
-use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tracing::error;
 
-use crate::frame::ServerFrames;
+use crate::{connection::Outbound, frame::ServerFrames};
 
+/// The heartbeat a client asked for, sent until it is dropped.
 pub(crate) struct Heartbeat {
-	is_running: bool,
-	interval: Duration,
-	message: mpsc::Sender<ServerFrames>,
+	task: Option<JoinHandle<()>>,
 }
 
 impl Heartbeat {
-	pub(crate) fn new(interval: u32, message: mpsc::Sender<ServerFrames>) -> Self {
-		Self {
-			is_running: false,
-			interval: Duration::from_millis((interval * 100) as u64),
-			message,
+	/// Sends a heartbeat every `interval` deciseconds, never for an interval
+	/// of 0.
+	pub(crate) fn start(interval: u32, outbound: Outbound) -> Self {
+		if interval == 0 {
+			return Self { task: None };
 		}
-	}
 
-	pub(crate) async fn start(&mut self) {
-		if self.is_running {
-			let _ = self.message.send(ServerFrames::Error {
-				msg: "Heartbeat alreadt exists".to_string(),
-			});
-			return;
-		}
+		let interval = Duration::from_millis(interval as u64 * 100);
+		let task = tokio::spawn(async move {
+			let mut interval = tokio::time::interval(interval);
 
-		self.is_running = true;
+			interval.tick().await;
 
-		let mut interval = tokio::time::interval(self.interval);
+			loop {
+				interval.tick().await;
+				if outbound.send(ServerFrames::Heartbeat).await.is_err() {
+					error!("Error sending heartbeat, connection is closed");
+					return;
+				}
+			}
+		});
 
-		interval.tick().await;
+		Self { task: Some(task) }
+	}
+}
 
-		loop {
-			interval.tick().await;
-			if let Err(e) = self.message.send(ServerFrames::Heartbeat).await {
-				error!("Error sending heartbeat: {}", e);
-				return;
-			}
+impl Drop for Heartbeat {
+	fn drop(&mut self) {
+		if let Some(task) = &self.task {
+			task.abort();
 		}
 	}
 }
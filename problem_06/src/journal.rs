@@ -0,0 +1,253 @@
+use std::{
+	collections::HashMap,
+	fs::{self, File, OpenOptions},
+	io::{self, Read, Write},
+	mem,
+	net::SocketAddr,
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+	sync::{mpsc, oneshot},
+	task,
+};
+use tracing::{error, warn};
+
+use crate::db::Ticket;
+
+/// A change to the [`Db`](crate::db::Db) state that has to survive a
+/// restart, stored as one JSON object per line.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "entry", rename_all = "snake_case")]
+pub(crate) enum Entry {
+	Observation {
+		plate: String,
+		road: u16,
+		mile: u16,
+		timestamp: u32,
+		/// The address of the camera, missing in older journals.
+		#[serde(default)]
+		camera: Option<SocketAddr>,
+	},
+	TicketedDay {
+		plate: String,
+		day: u32,
+	},
+	/// An issued ticket, open until it is delivered or dropped.
+	OpenTicket(Ticket),
+	/// A dispatcher wrote the ticket to its client.
+	TicketDelivered(Ticket),
+	/// An operator dropped the open ticket.
+	TicketDropped(Ticket),
+	/// Observations on `road` before `timestamp` were dropped.
+	Cutoff {
+		road: u16,
+		timestamp: u32,
+	},
+	/// Ticketed days before `day` were dropped.
+	FirstDay {
+		day: u32,
+	},
+}
+
+/// What the [`write`] task does with the journal, in the order sent.
+#[derive(Debug)]
+pub(crate) enum Command {
+	Append(Entry),
+	Compact,
+	/// Answered once every command sent before it is done.
+	Flush(oneshot::Sender<()>),
+}
+
+/// The most commands written with one blocking call.
+const BATCH: usize = 256;
+
+#[derive(Debug)]
+pub(crate) struct Journal {
+	path: PathBuf,
+	file: File,
+	/// Entries appended since the journal was opened or compacted.
+	appended: usize,
+}
+
+impl Journal {
+	/// Opens the journal at `path`, creating it if needed, and returns the
+	/// entries it already holds.
+	///
+	/// Lines that do not parse, like one cut short by a crash, are skipped.
+	pub(crate) fn open(path: &Path) -> io::Result<(Journal, Vec<Entry>)> {
+		let mut file = OpenOptions::new()
+			.create(true)
+			.read(true)
+			.append(true)
+			.open(path)?;
+
+		let mut content = Vec::new();
+		file.read_to_end(&mut content)?;
+		let entries = parse(&content, path);
+
+		// Terminate a line cut short by a crash, or the next entry would be
+		// appended to it.
+		if content.last().is_some_and(|b| *b != b'\n') {
+			file.write_all(b"\n")?;
+		}
+
+		let journal = Journal {
+			path: path.to_path_buf(),
+			file,
+			appended: 0,
+		};
+		Ok((journal, entries))
+	}
+
+	/// Writes `entry` with a single write, so a crash loses at most the
+	/// entry being written.
+	pub(crate) fn append(&mut self, entry: &Entry) -> io::Result<()> {
+		let mut line = serde_json::to_vec(entry)?;
+		line.push(b'\n');
+		self.file.write_all(&line)?;
+		self.appended += 1;
+		Ok(())
+	}
+
+	/// Rewrites the journal with only the entries that still matter, through
+	/// a temporary file so a crash leaves either the old or the new journal.
+	/// Does nothing if no entry was appended since the last time.
+	pub(crate) fn compact(&mut self) -> io::Result<()> {
+		if self.appended == 0 {
+			return Ok(());
+		}
+
+		let mut content = Vec::new();
+		File::open(&self.path)?.read_to_end(&mut content)?;
+		let entries = live(parse(&content, &self.path));
+
+		let tmp = self.path.with_extension("tmp");
+		let mut file = File::create(&tmp)?;
+		for entry in &entries {
+			let mut line = serde_json::to_vec(entry)?;
+			line.push(b'\n');
+			file.write_all(&line)?;
+		}
+		file.sync_all()?;
+		fs::rename(&tmp, &self.path)?;
+
+		self.file = OpenOptions::new().append(true).open(&self.path)?;
+		self.appended = 0;
+		Ok(())
+	}
+}
+
+/// Runs `commands` against `journal` until every sender is dropped. The file
+/// is only touched on the blocking pool, so no connection waits on the disk.
+pub(crate) async fn write(mut journal: Journal, mut commands: mpsc::UnboundedReceiver<Command>) {
+	let mut batch = Vec::with_capacity(BATCH);
+
+	while commands.recv_many(&mut batch, BATCH).await > 0 {
+		let batch = mem::take(&mut batch);
+		let written = task::spawn_blocking(move || {
+			for command in batch {
+				match command {
+					Command::Append(entry) => {
+						if let Err(e) = journal.append(&entry) {
+							error!("Could not write to journal: {e}");
+						}
+					}
+					Command::Compact => {
+						if let Err(e) = journal.compact() {
+							error!("Could not compact the journal: {e}");
+						}
+					}
+					Command::Flush(done) => {
+						let _ = done.send(());
+					}
+				}
+			}
+			journal
+		})
+		.await;
+
+		journal = match written {
+			Ok(journal) => journal,
+			Err(e) => {
+				error!("The journal writer stopped: {e}");
+				return;
+			}
+		};
+	}
+}
+
+/// The entries in the lines of `content`, without the lines that do not parse.
+fn parse(content: &[u8], path: &Path) -> Vec<Entry> {
+	let mut entries = Vec::new();
+
+	for (number, line) in content.split(|b| *b == b'\n').enumerate() {
+		if line.is_empty() {
+			continue;
+		}
+		match serde_json::from_slice(line) {
+			Ok(entry) => entries.push(entry),
+			Err(e) => warn!("Skipping line {} of journal {path:?}: {e}", number + 1),
+		}
+	}
+
+	entries
+}
+
+/// The entries that restore the same state as `entries`: without the
+/// observations and ticketed days a cutoff dropped, and without the tickets
+/// that were delivered or dropped. The cutoffs come first, so replaying them
+/// ignores older entries appended later.
+fn live(entries: Vec<Entry>) -> Vec<Entry> {
+	let mut cutoffs: HashMap<u16, u32> = HashMap::new();
+	let mut first_day = 0;
+	let mut closed: HashMap<Ticket, usize> = HashMap::new();
+
+	for entry in &entries {
+		match entry {
+			Entry::Cutoff { road, timestamp } => {
+				let cutoff = cutoffs.entry(*road).or_default();
+				*cutoff = (*cutoff).max(*timestamp);
+			}
+			Entry::FirstDay { day } => first_day = first_day.max(*day),
+			Entry::TicketDelivered(ticket) | Entry::TicketDropped(ticket) => {
+				*closed.entry(ticket.clone()).or_default() += 1;
+			}
+			_ => {}
+		}
+	}
+
+	let mut live: Vec<_> = cutoffs
+		.iter()
+		.map(|(&road, &timestamp)| Entry::Cutoff { road, timestamp })
+		.collect();
+	if first_day > 0 {
+		live.push(Entry::FirstDay { day: first_day });
+	}
+
+	for entry in entries {
+		let keep = match &entry {
+			Entry::Observation {
+				road, timestamp, ..
+			} => *timestamp >= cutoffs.get(road).copied().unwrap_or_default(),
+			Entry::TicketedDay { day, .. } => *day >= first_day,
+			Entry::OpenTicket(ticket) => match closed.get_mut(ticket) {
+				Some(count) if *count > 0 => {
+					*count -= 1;
+					false
+				}
+				_ => true,
+			},
+			Entry::TicketDelivered(_)
+			| Entry::TicketDropped(_)
+			| Entry::Cutoff { .. }
+			| Entry::FirstDay { .. } => false,
+		};
+		if keep {
+			live.push(entry);
+		}
+	}
+
+	live
+}
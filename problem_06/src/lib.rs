@@ -1,16 +1,24 @@
+mod admin;
+pub mod audit;
+mod compaction;
+mod config;
 mod connection;
 mod db;
-mod frame;
+pub mod frame;
 mod heartbeat;
+mod journal;
+mod metrics;
+pub mod observations;
+pub mod policy;
 pub mod server;
-mod shutdown;
 mod ticketing;
 
+pub use audit::AuditFormat;
+pub use config::{Config, Dispatch};
 pub use connection::Connection;
-pub use frame::ClientFrames;
-use shutdown::Shutdown;
+pub use frame::{ClientFrames, Decode, Encode, FrameError, ServerFrames};
 
-pub const DEFAULT_IP: &'static str = "0.0.0.0";
+pub const MAX_CONNECTIONS: usize = 1500;
 pub const DEFAULT_PORT: u16 = 1222;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -0,0 +1,61 @@
+use protohackers_core::metrics::{Counter, CounterVec, Gauge, Registry};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Metrics {
+	pub(crate) frames_received: CounterVec,
+	pub(crate) parse_errors: Counter,
+	pub(crate) tickets_issued: Counter,
+	pub(crate) bytes_received: Counter,
+	pub(crate) bytes_sent: Counter,
+	pub(crate) observations_ignored: Counter,
+	pub(crate) conflicting_limits: Counter,
+	pub(crate) observations_held: Gauge,
+	pub(crate) ticketed_days_held: Gauge,
+	pub(crate) observations_evicted: Counter,
+	pub(crate) ticketed_days_evicted: Counter,
+	pub(crate) disconnects: CounterVec,
+}
+
+impl Metrics {
+	pub(crate) fn new(registry: &Registry) -> Metrics {
+		Metrics {
+			frames_received: registry.counter_vec(
+				"frames_received_total",
+				"Frames parsed, by frame type.",
+				"type",
+			),
+			parse_errors: registry.counter("parse_errors_total", "Frames that failed to parse."),
+			tickets_issued: registry
+				.counter("tickets_issued_total", "Tickets issued for speeding."),
+			bytes_received: registry.counter("bytes_received_total", "Bytes read from clients."),
+			bytes_sent: registry.counter("bytes_sent_total", "Bytes written to clients."),
+			observations_ignored: registry.counter(
+				"observations_ignored_total",
+				"Plate observations ignored as the plate was already seen on the road at that time, or older than the retention.",
+			),
+			conflicting_limits: registry.counter(
+				"conflicting_limits_total",
+				"Cameras that reported another limit than the cameras before them on the same road.",
+			),
+			observations_held: registry
+				.gauge("observations_held", "Plate observations kept in memory."),
+			ticketed_days_held: registry.gauge(
+				"ticketed_days_held",
+				"Days on which a plate got a ticket, kept in memory.",
+			),
+			observations_evicted: registry.counter(
+				"observations_evicted_total",
+				"Plate observations dropped outside the retention.",
+			),
+			ticketed_days_evicted: registry.counter(
+				"ticketed_days_evicted_total",
+				"Ticketed days dropped outside the retention.",
+			),
+			disconnects: registry.counter_vec(
+				"slow_disconnects_total",
+				"Clients disconnected for being too slow, by reason.",
+				"reason",
+			),
+		}
+	}
+}
@@ -0,0 +1,106 @@
+//! The observations of one plate on one road, ordered by time.
+//!
+//! A car is only ever ticketed for two observations next to each other in
+//! time, so a new observation is compared against its predecessor and
+//! successor instead of every earlier one.
+
+use std::{
+	collections::BTreeMap,
+	net::SocketAddr,
+	ops::Bound::{Excluded, Unbounded},
+};
+
+/// The connection of a camera.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct CameraId(pub SocketAddr);
+
+/// A camera at `mile` saw the plate at `timestamp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observation {
+	pub mile: u16,
+	pub timestamp: u32,
+}
+
+/// The observations right before and after a new one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbours {
+	pub before: Option<Observation>,
+	pub after: Option<Observation>,
+}
+
+impl Neighbours {
+	pub fn iter(&self) -> impl Iterator<Item = Observation> {
+		self.before.into_iter().chain(self.after)
+	}
+}
+
+/// The mile and, unless it was replayed from a journal that did not record
+/// it, the camera of each observation by timestamp.
+#[derive(Debug, Default)]
+pub struct Timeline {
+	miles: BTreeMap<u32, (u16, Option<CameraId>)>,
+}
+
+impl Timeline {
+	/// Adds the observation of `camera` and returns its neighbours in time.
+	/// A second observation at the same timestamp is ignored and has no
+	/// neighbours.
+	pub fn insert(&mut self, mile: u16, timestamp: u32, camera: Option<CameraId>) -> Neighbours {
+		if self.miles.contains_key(&timestamp) {
+			return Neighbours::default();
+		}
+		self.miles.insert(timestamp, (mile, camera));
+
+		Neighbours {
+			before: self
+				.miles
+				.range(..timestamp)
+				.next_back()
+				.map(Self::observation),
+			after: self
+				.miles
+				.range((Excluded(timestamp), Unbounded))
+				.next()
+				.map(Self::observation),
+		}
+	}
+
+	fn observation((&timestamp, &(mile, _)): (&u32, &(u16, Option<CameraId>))) -> Observation {
+		Observation { mile, timestamp }
+	}
+
+	/// The mile of the observation at `timestamp`.
+	pub fn mile_at(&self, timestamp: u32) -> Option<u16> {
+		self.miles.get(&timestamp).map(|&(mile, _)| mile)
+	}
+
+	/// The camera of the observation at `timestamp`, if it is known.
+	pub fn camera_at(&self, timestamp: u32) -> Option<CameraId> {
+		self.miles.get(&timestamp).and_then(|&(_, camera)| camera)
+	}
+
+	/// Drops the observations before `timestamp` and returns how many.
+	pub fn retain_since(&mut self, timestamp: u32) -> usize {
+		let before = self.miles.len();
+		self.miles = self.miles.split_off(&timestamp);
+		before - self.miles.len()
+	}
+
+	/// All observations, the earliest first.
+	pub fn iter(&self) -> impl Iterator<Item = Observation> + '_ {
+		self.miles.iter().map(Self::observation)
+	}
+
+	/// The timestamp of the latest observation.
+	pub fn latest(&self) -> Option<u32> {
+		self.miles.last_key_value().map(|(&timestamp, _)| timestamp)
+	}
+
+	pub fn len(&self) -> usize {
+		self.miles.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.miles.is_empty()
+	}
+}
@@ -0,0 +1,67 @@
+//! When a pair of observations of a car gets a ticket, free of any state.
+
+use std::ops::RangeInclusive;
+
+use crate::observations::Observation;
+
+pub const SECONDS_PER_DAY: u32 = 86400;
+
+/// Cars faster than the limit by this many hundredths of a mile per hour
+/// are always ticketed, slower ones never.
+pub const TOLERANCE: u64 = 50;
+
+/// The speed limit of a road.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TicketPolicy {
+	pub limit: u16,
+}
+
+/// The car was too fast between two observations, ordered by time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Speeding {
+	pub first: Observation,
+	pub second: Observation,
+	/// Average speed in hundredths of a mile per hour, `u16::MAX` if it does
+	/// not fit.
+	pub speed: u16,
+	/// Every day the ticket covers, a car gets at most one ticket a day.
+	pub days: RangeInclusive<u32>,
+}
+
+impl TicketPolicy {
+	pub fn new(limit: u16) -> TicketPolicy {
+		TicketPolicy { limit }
+	}
+
+	/// Checks the average speed between two observations given in any order.
+	/// Observations at the same time cannot be compared and never lead to a
+	/// ticket.
+	pub fn check(&self, a: Observation, b: Observation) -> Option<Speeding> {
+		let (first, second) = if a.timestamp <= b.timestamp {
+			(a, b)
+		} else {
+			(b, a)
+		};
+
+		let distance = first.mile.abs_diff(second.mile) as u64;
+		let time = (second.timestamp - first.timestamp) as u64;
+		if time == 0 {
+			return None;
+		}
+
+		// distance / time * 3600 * 100 >= limit * 100 + tolerance, without
+		// rounding.
+		if distance * 3600 * 100 < (self.limit as u64 * 100 + TOLERANCE) * time {
+			return None;
+		}
+
+		let speed = (distance * 3600 * 100 + time / 2) / time;
+
+		Some(Speeding {
+			first,
+			second,
+			speed: u16::try_from(speed).unwrap_or(u16::MAX),
+			days: first.timestamp / SECONDS_PER_DAY..=second.timestamp / SECONDS_PER_DAY,
+		})
+	}
+}
@@ -1,253 +1,336 @@
-use std::{future::Future, sync::Arc};
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
 
+use protohackers_core::{metrics::Registry, ConnectionHandler, Server, ServerConfig, Shutdown};
 use tokio::{
 	net::{TcpListener, TcpStream},
-	sync::{broadcast, mpsc, Mutex, Semaphore},
-	time::{self, Duration},
+	sync::mpsc,
 };
-use tracing::{error, info};
+use tracing::{info, warn};
 
 use crate::{
-	connection::ConnectionType,
-	db::{Camera, CameraId, Db, DispatcherId, Limit, Mile, Plate, PlateName, Road, Timestamp},
-	frame::{ClientFrames, ServerFrames},
+	admin,
+	audit::AuditLog,
+	compaction,
+	connection::{ConnectionType, Outbound, SlowClient, Timeouts},
+	db::{Camera, Db, DispatcherId, Limit, Mile, Plate, PlateName, Road, Ticket, Timestamp},
+	frame::{ClientFrames, FrameError, ServerFrames},
 	heartbeat::Heartbeat,
-	ticketing::{issue_possible_ticket, send_out_waiting_tickets},
-	Connection, Shutdown,
+	metrics::Metrics,
+	observations::CameraId,
+	ticketing::{issue_possible_ticket, return_undelivered_tickets, send_out_waiting_tickets},
+	Config, Connection,
 };
 
-struct Listener {
-	listener: TcpListener,
-	db: Arc<Mutex<Db>>,
-	limit_connections: Arc<Semaphore>,
-	notify_shutdown: broadcast::Sender<()>,
-	shutdown_complete_tx: mpsc::Sender<()>,
+/// State shared by all connections, creates a [`Handler`] per connection.
+struct SpeedDaemon {
+	db: Arc<Db>,
+	metrics: Metrics,
+	timeouts: Timeouts,
+	outbound_queue: usize,
 }
 
 struct Handler {
 	connection: Connection,
 	connection_type: Option<ConnectionType>,
-	db: Arc<Mutex<Db>>,
+	db: Arc<Db>,
+	metrics: Metrics,
 	shutdown: Shutdown,
-	_shutdown_complete: mpsc::Sender<()>,
+	/// Set once the client asked for heartbeats, stops them when dropped.
+	heartbeat: Option<Heartbeat>,
+	/// Tickets taken from the queue whose write to the client failed.
+	undelivered: Vec<Ticket>,
+	outbound_queue: usize,
 }
 
-const MAX_CONNECTIONS: usize = 1500;
-
-pub async fn run(listener: TcpListener, shutdown: impl Future) -> crate::Result<()> {
-	let (notify_shutdown, _) = broadcast::channel(1);
-	let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel(1);
+pub async fn run(
+	listener: TcpListener,
+	config: Config,
+	shutdown: impl Future,
+) -> crate::Result<()> {
+	let db = match &config.journal {
+		Some(path) => Db::open(path, config.dispatch)
+			.map_err(|e| format!("cannot open journal {path:?}: {e}"))?,
+		None => Db::new(config.dispatch),
+	};
+	let db = match &config.audit_log {
+		Some(path) => db.with_audit_log(
+			AuditLog::open(path, config.audit_format)
+				.map_err(|e| format!("cannot open audit log {path:?}: {e}"))?,
+		),
+		None => db,
+	};
 
-	let mut server = Listener {
-		listener,
-		db: Arc::new(Mutex::new(Db::new())),
-		limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
-		notify_shutdown: notify_shutdown.clone(),
-		shutdown_complete_tx,
+	let registry = Registry::new("speed_daemon");
+	let speed_daemon = SpeedDaemon {
+		db: Arc::new(db),
+		metrics: Metrics::new(&registry),
+		timeouts: Timeouts {
+			idle: config.idle_timeout_secs.map(Duration::from_secs),
+			frame: Duration::from_secs(config.frame_timeout_secs),
+			write: Duration::from_secs(config.write_timeout_secs),
+		},
+		outbound_queue: config.outbound_queue,
 	};
 
-	tokio::select! {
-		res = server.run() => {
-			if let Err(err) = res {
-				error!(cause = %err, "failed to accept");
+	let compaction = tokio::spawn(compaction::run(
+		speed_daemon.db.clone(),
+		config.retention_days,
+		Duration::from_secs(config.compaction_interval_secs.max(1)),
+		speed_daemon.metrics.clone(),
+	));
+
+	let admin = match config.admin_port {
+		Some(port) => {
+			let listener = ServerConfig {
+				ip: config.admin_ip,
+				port,
+				..config.server.clone()
 			}
+			.bind()
+			.await?;
+			info!("Serve the admin API on {}", listener.local_addr()?);
+			Some(tokio::spawn(admin::serve(
+				listener,
+				speed_daemon.db.clone(),
+			)))
 		}
-		_ = shutdown => {
-			// Shutdown signal received
-		}
-	}
-
-	let Listener {
-		shutdown_complete_tx,
-		notify_shutdown,
-		..
-	} = server;
+		None => None,
+	};
 
-	drop(notify_shutdown);
-	drop(shutdown_complete_tx);
+	let db = speed_daemon.db.clone();
+	let res = Server::new(listener, speed_daemon)
+		.max_connections(config.server.max_connections)
+		.metrics(registry, config.server.bind_metrics().await?)
+		.run(shutdown)
+		.await;
 
-	let _ = shutdown_complete_rx.recv().await;
+	compaction.abort();
+	if let Some(admin) = admin {
+		admin.abort();
+	}
+	db.flush_journal().await;
 
-	Ok(())
+	res
 }
 
-impl Listener {
-	async fn run(&mut self) -> crate::Result<()> {
-		loop {
-			let permit = self
-				.limit_connections
-				.clone()
-				.acquire_owned()
-				.await
-				.unwrap();
-
-			let socket = self.accept().await?;
-			let address = socket.peer_addr()?;
-
-			let mut handler = Handler {
-				connection: Connection::new(address, socket),
-				connection_type: None,
-				db: self.db.clone(),
-				shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
-				_shutdown_complete: self.shutdown_complete_tx.clone(),
-			};
+impl ConnectionHandler for SpeedDaemon {
+	async fn handle(
+		&self,
+		socket: TcpStream,
+		address: SocketAddr,
+		shutdown: Shutdown,
+	) -> crate::Result<()> {
+		let mut handler = Handler {
+			outbound_queue: self.outbound_queue,
+			connection: Connection::new(address, socket, self.metrics.clone(), self.timeouts),
+			connection_type: None,
+			db: self.db.clone(),
+			metrics: self.metrics.clone(),
+			shutdown,
+			heartbeat: None,
+			undelivered: Vec::new(),
+		};
 
-			tokio::spawn(async move {
-				if let Err(err) = handler.run().await {
-					error!(cause = ?err, "connection error");
-				}
-				drop(permit);
-			});
-		}
+		handler.run().await
 	}
+}
 
-	async fn accept(&mut self) -> crate::Result<TcpStream> {
-		let mut backoff = 1;
-
-		loop {
-			match self.listener.accept().await {
-				Ok((socket, _)) => return Ok(socket),
-				Err(err) => {
-					if backoff > 64 {
-						return Err(err.into());
-					}
-				}
-			}
+impl Handler {
+	async fn run(&mut self) -> crate::Result<()> {
+		let (outbound, mut receive_message) = Outbound::new(self.outbound_queue);
 
-			time::sleep(Duration::from_secs(backoff)).await;
+		let res = self.serve(outbound, &mut receive_message).await;
+		self.heartbeat = None;
 
-			backoff *= 2;
+		match &self.connection_type {
+			Some(ConnectionType::Camera(camera)) => {
+				self.db
+					.remove_camera(&CameraId(self.connection.get_address()), &camera.road);
+			}
+			Some(ConnectionType::Dispatcher) => {
+				self.disconnect_dispatcher(receive_message).await;
+			}
+			None => {}
 		}
-	}
-}
 
-impl Handler {
-	async fn run(&mut self) -> crate::Result<()> {
-		let (send_message, mut receive_message): (
-			mpsc::Sender<ServerFrames>,
-			mpsc::Receiver<ServerFrames>,
-		) = mpsc::channel(1024);
+		res
+	}
 
+	async fn serve(
+		&mut self,
+		outbound: Outbound,
+		receive_message: &mut mpsc::Receiver<ServerFrames>,
+	) -> crate::Result<()> {
 		while !self.shutdown.is_shutdown() {
+			// Shutdown comes first and outbound frames before client input,
+			// so neither waits behind a chatty client. Queued frames are
+			// written before a lag is acted on, so a lagging dispatcher still
+			// delivers the tickets it already took and only the ones that did
+			// not fit go to another.
 			tokio::select! {
+				biased;
+				_ = self.shutdown.recv() => {
+					return Ok(());
+				}
+				message = receive_message.recv() => {
+					if let Some(message) = message {
+						if let Err(e) = self.connection.write_frame(message.clone()).await {
+							self.undelivered.extend(Ticket::try_from(message).ok());
+							if let Some(&slow) = e.downcast_ref::<SlowClient>() {
+								return self.disconnect_slow_client(slow).await;
+							}
+							return Err(e);
+						}
+						if let Ok(ticket) = Ticket::try_from(message) {
+							self.db.ticket_delivered(&ticket);
+						}
+					}
+				}
+				_ = outbound.lagging() => {
+					return self.disconnect_slow_client(SlowClient::Lagging).await;
+				}
 				res = self.connection.read_frame() => {
 					match res {
-					   Ok(Some(frame)) => {
-							if let Err(e) = self.handle_client_frame(self.db.clone(), frame, send_message.clone()).await {
-								error!("Error handling frame: {e:?}");
-							  }
+						Ok(Some(frame)) => {
+							// A client that breaks the protocol is told why and
+							// disconnected.
+							if let Err(e) = self.handle_client_frame(frame, outbound.clone()).await {
+								info!("Disconnecting client: {e}");
+								let _ = self.connection.write_frame(ServerFrames::Error { msg: e.to_string() }).await;
+								return Ok(());
+							}
 						},
 						Ok(None) => return Ok(()),
-						Err(_) => {
-							let _ = self.connection.write_frame(ServerFrames::Error { msg: "Not supported frame sent".to_string() }).await;
+						Err(e) => {
+							// Malformed input and slow clients end the
+							// connection, with an Error frame that tells the
+							// client why.
+							if let Some(&slow) = e.downcast_ref::<SlowClient>() {
+								return self.disconnect_slow_client(slow).await;
+							}
+							let Some(e) = e.downcast_ref::<FrameError>() else {
+								return Err(e);
+							};
+							let _ = self.connection.write_frame(ServerFrames::Error { msg: e.to_string() }).await;
 							return Ok(());
 						}
 					}
 				}
-				message = receive_message.recv() => {
-					match message {
-						Some(message) => {
-							let _ = self.connection.write_frame(message).await;
-						},
-						None => (),
-					}
-				}
-				_ = self.shutdown.recv() => {
-					return Ok(());
-				}
 			};
 		}
 
 		Ok(())
 	}
 
-	fn set_connection_type(&mut self, connection_type: ConnectionType) {
-		match connection_type {
-			ConnectionType::Camera => {
-				self.connection_type = Some(connection_type);
-			}
-			ConnectionType::Dispatcher => {
-				self.connection_type = Some(connection_type);
-			}
+	async fn disconnect_slow_client(&mut self, reason: SlowClient) -> crate::Result<()> {
+		info!("Disconnecting {}: {reason}", self.connection.get_address());
+		self.metrics.disconnects.with(reason.label()).inc();
+		// A client that does not read will not take the Error frame either,
+		// the write timeout bounds the attempt.
+		if reason != SlowClient::Write {
+			let _ = self
+				.connection
+				.write_frame(ServerFrames::Error {
+					msg: reason.to_string(),
+				})
+				.await;
 		}
+		Ok(())
+	}
+
+	/// Removes the dispatcher so no more tickets are sent to it and passes on
+	/// the tickets it did not write to its client.
+	async fn disconnect_dispatcher(&mut self, mut receive_message: mpsc::Receiver<ServerFrames>) {
+		self.db
+			.remove_dispatcher(&DispatcherId(self.connection.get_address()));
+
+		// Tickets sent before the removal are still queued, nothing new can
+		// arrive once the channel is closed.
+		receive_message.close();
+
+		let mut tickets = std::mem::take(&mut self.undelivered);
+		while let Ok(message) = receive_message.try_recv() {
+			tickets.extend(Ticket::try_from(message).ok());
+		}
+
+		if !tickets.is_empty() {
+			return_undelivered_tickets(&self.db, tickets).await;
+		}
+	}
+
+	fn set_connection_type(&mut self, connection_type: ConnectionType) {
+		self.connection_type = Some(connection_type);
 	}
 
 	async fn handle_client_frame(
 		&mut self,
-		db: Arc<Mutex<Db>>,
 		frame: ClientFrames,
-		send_message: mpsc::Sender<ServerFrames>,
+		outbound: Outbound,
 	) -> crate::Result<()> {
+		let frame_type = match frame {
+			ClientFrames::Plate { .. } => "plate",
+			ClientFrames::WantHeartbeat { .. } => "want_heartbeat",
+			ClientFrames::IAmCamera { .. } => "i_am_camera",
+			ClientFrames::IAmDispatcher { .. } => "i_am_dispatcher",
+		};
+		self.metrics.frames_received.with(frame_type).inc();
+
 		match frame {
 			ClientFrames::Plate { plate, timestamp } => {
-				if self.connection_type.is_some()
-					&& self.connection_type == Some(ConnectionType::Camera)
-				{
+				if let Some(ConnectionType::Camera(camera)) = &self.connection_type {
 					info!("Receive new plate: {plate} at {timestamp}");
 					issue_possible_ticket(
-						db,
+						&self.db,
 						Plate {
 							plate: PlateName(plate.clone()),
 							timestamp: Timestamp(timestamp),
 						},
 						CameraId(self.connection.get_address()),
+						camera,
+						&self.metrics,
 					)
 					.await;
 				} else {
-					let _ = send_message
-						.send(ServerFrames::Error {
-							msg: "Not connected as camera".to_string(),
-						})
-						.await;
-					return Err("Already connected".into());
+					return Err("Not connected as camera".into());
 				}
 			}
 			ClientFrames::WantHeartbeat { interval } => {
-				if interval > 0 {
-					tokio::spawn(async move {
-						let mut heartbeat = Heartbeat::new(interval, send_message.clone());
-						heartbeat.start().await;
-					});
+				if self.heartbeat.is_some() {
+					return Err("Heartbeat already requested".into());
 				}
+				self.heartbeat = Some(Heartbeat::start(interval, outbound));
 			}
 			ClientFrames::IAmCamera { road, mile, limit } => {
 				info!("Receive new camera: {road} at {mile} with limit {limit}");
 				if self.connection_type.is_some() {
-					let _ = send_message
-						.send(ServerFrames::Error {
-							msg: "Already connected as a connection type".to_string(),
-						})
-						.await;
-					return Err("Already connected".into());
+					return Err("Already connected as a connection type".into());
 				}
-				self.set_connection_type(ConnectionType::Camera);
-
-				db.lock().await.add_camera(
-					CameraId(self.connection.get_address()),
-					Camera {
-						road: Road(road),
-						mile: Mile(mile),
-						limit: Limit(limit),
-					},
-				);
+				let camera = Camera {
+					road: Road(road),
+					mile: Mile(mile),
+					limit: Limit(limit),
+				};
+				let conflicting = self
+					.db
+					.add_camera(CameraId(self.connection.get_address()), camera.clone());
+				if let Some(limit) = conflicting {
+					warn!("{camera:?} reports another limit than {limit:?} of the other cameras on its road");
+					self.metrics.conflicting_limits.inc();
+				}
+				self.set_connection_type(ConnectionType::Camera(camera));
 			}
 			ClientFrames::IAmDispatcher { roads } => {
 				if self.connection_type.is_some() {
-					let _ = send_message
-						.send(ServerFrames::Error {
-							msg: "Already connected as a connection type".to_string(),
-						})
-						.await;
-					return Err("Already connected".into());
+					return Err("Already connected as a connection type".into());
 				}
 
 				self.set_connection_type(ConnectionType::Dispatcher);
-				db.lock().await.add_dispatcher(
+				let waiting = self.db.add_dispatcher(
 					DispatcherId(self.connection.get_address()),
 					roads.to_vec(),
-					send_message.clone(),
+					outbound,
 				);
-				send_out_waiting_tickets(db).await;
+				send_out_waiting_tickets(&self.db, waiting).await;
 			}
 		}
 
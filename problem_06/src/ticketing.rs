@@ -1,98 +1,107 @@
-use std::sync::Arc;
-
-use tokio::sync::Mutex;
 use tracing::info;
 
-use crate::db::{CameraId, Db, Plate, Road, Ticket};
-
-pub(crate) async fn issue_possible_ticket(db: Arc<Mutex<Db>>, plate: Plate, camera_id: CameraId) {
-	let mut db = db.lock().await;
-	let camera = db.get_camera(camera_id.clone()).unwrap();
-	let observed_plates = db.get_plates_by_road(plate.clone(), camera.road.clone());
-
-	if observed_plates.is_none() {
-		db.add_plate(camera_id, plate);
+use crate::{
+	audit::{AuditRecord, Event},
+	db::{Camera, Db, Plate, Ticket},
+	metrics::Metrics,
+	observations::{CameraId, Observation},
+	policy::TicketPolicy,
+};
+
+pub(crate) async fn issue_possible_ticket(
+	db: &Db,
+	plate: Plate,
+	camera_id: CameraId,
+	camera: &Camera,
+	metrics: &Metrics,
+) {
+	let Some(neighbours) = db.add_observation(camera_id, camera, &plate) else {
+		metrics.observations_ignored.inc();
 		return;
-	}
+	};
 
-	let mile = camera.mile;
-	let limit = camera.limit;
-	let road = camera.road;
+	let policy = TicketPolicy::new(camera.limit.0);
+	let observation = Observation {
+		mile: camera.mile.0,
+		timestamp: plate.timestamp.0,
+	};
 
-	let plate_name = plate.clone().plate;
-	let timestamp = plate.clone().timestamp;
-
-	for (m, t) in observed_plates.unwrap().iter() {
-		let distance = if mile > *m {
-			mile.0 - m.0
-		} else {
-			m.0 - mile.0
-		};
-
-		let (time, mile1, timestamp1, mile2, timestamp2) = if timestamp > *t {
-			(timestamp.0 - t.0, m.0, t.0, mile.0, timestamp.0)
-		} else {
-			(t.0 - timestamp.0, mile.0, timestamp.0, m.0, t.0)
+	for neighbour in neighbours.iter() {
+		let Some(speeding) = policy.check(observation, neighbour) else {
+			continue;
 		};
 
-		let speed = (distance as u64 * 3600 * 100 / time as u64) as u16;
-
-		if speed > limit.0 * 100 {
-			let ticket = Ticket {
-				plate: plate_name.clone().0,
-				road: road.0,
-				mile1,
-				timestamp1,
-				mile2,
-				timestamp2,
-				speed,
-			};
-
-			let day_start = timestamp1 / 86400;
-			let day_end = timestamp2 / 86400;
-
-			let spans_multiple_days = day_start != day_end;
-
-			if spans_multiple_days
-				&& (db.is_plate_ticketed_for_day(day_start, plate_name.clone())
-					|| db.is_plate_ticketed_for_day(day_end, plate_name.clone()))
-			{
-				continue;
-			}
-
-			if db.is_plate_ticketed_for_day(day_start, plate_name.clone()) {
-				continue;
-			}
-
-			for day in day_start..=day_end {
-				info!("Ticket for day {day} for {ticket:?}");
-				db.ticket_plate(day, plate_name.clone());
-			}
-
-			let dispatcher = db.get_dispatcher_for_road(road.clone());
+		if !db.ticket_plate(speeding.days.clone(), &plate.plate) {
+			continue;
+		}
 
-			if dispatcher.is_none() {
-				info!("No dispatcher yet for this road: {ticket:?}");
-				db.add_open_ticket(ticket.clone());
-				continue;
-			}
+		let ticket = Ticket {
+			plate: plate.plate.0.clone(),
+			road: camera.road.0,
+			mile1: speeding.first.mile,
+			timestamp1: speeding.first.timestamp,
+			mile2: speeding.second.mile,
+			timestamp2: speeding.second.timestamp,
+			speed: speeding.speed,
+		};
 
-			info!("Sending ticket: {ticket:?}");
-			let _ = dispatcher.unwrap().send(ticket.clone().into()).await;
-		}
+		info!("Ticket for days {:?} for {ticket:?}", speeding.days);
+		metrics.tickets_issued.inc();
+		db.open_ticket(&ticket);
+
+		db.audit(AuditRecord {
+			camera1: db
+				.camera_at(&camera.road, &plate.plate, speeding.first.timestamp)
+				.map(|id| id.0.to_string()),
+			camera2: db
+				.camera_at(&camera.road, &plate.plate, speeding.second.timestamp)
+				.map(|id| id.0.to_string()),
+			..AuditRecord::new(Event::Issued, &ticket)
+		});
+
+		dispatch(db, ticket).await;
 	}
-
-	db.add_plate(camera_id, plate);
 }
 
-pub(crate) async fn send_out_waiting_tickets(db: Arc<Mutex<Db>>) {
-	let mut db = db.lock().await;
-	let tickets = db.get_open_tickets();
+/// Sends the tickets that waited for the dispatcher that just connected.
+pub(crate) async fn send_out_waiting_tickets(db: &Db, tickets: Vec<Ticket>) {
 	info!("Sending out waiting tickets: {tickets:?}");
 	for ticket in tickets {
-		if let Some(dispatcher) = db.get_dispatcher_for_road(Road(ticket.road)) {
-			let _ = dispatcher.send(ticket.clone().into()).await;
-			db.remove_open_ticket(Road(ticket.road), ticket);
+		dispatch(db, ticket).await;
+	}
+}
+
+/// Hands tickets a dispatcher took but never wrote to its client to another
+/// dispatcher, or back to the open tickets.
+pub(crate) async fn return_undelivered_tickets(db: &Db, tickets: Vec<Ticket>) {
+	for ticket in tickets {
+		info!("Returning undelivered ticket: {ticket:?}");
+		dispatch(db, ticket).await;
+	}
+}
+
+/// Sends `ticket` to a dispatcher for its road. A dispatcher whose
+/// connection is gone or whose queue is full is removed and the next one is
+/// tried, without any the ticket waits in the open tickets.
+pub(crate) async fn dispatch(db: &Db, ticket: Ticket) {
+	loop {
+		let dispatchers = db.dispatchers_or_keep(&ticket);
+		if dispatchers.is_empty() {
+			info!("No dispatcher for this road: {ticket:?}");
+			db.audit(AuditRecord::new(Event::Queued, &ticket));
+			return;
+		}
+
+		for (dispatcher_id, dispatcher) in dispatchers {
+			info!("Sending ticket to {dispatcher_id:?}: {ticket:?}");
+			if dispatcher.try_send(ticket.clone().into()).is_ok() {
+				db.audit(AuditRecord {
+					dispatcher: Some(dispatcher_id.0.to_string()),
+					..AuditRecord::new(Event::Dispatched, &ticket)
+				});
+				return;
+			}
+			db.remove_dispatcher(&dispatcher_id);
 		}
 	}
 }
@@ -0,0 +1,199 @@
+use std::{
+	net::{Ipv4Addr, TcpListener},
+	time::Duration,
+};
+
+use problem_06::{server, ClientFrames, Config, Encode, ServerFrames};
+use protohackers_core::test_support::{BinaryClient, TestServer};
+use serde_json::{json, Value};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::TcpStream,
+};
+
+/// A port nothing listens on right now.
+fn free_port() -> u16 {
+	TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+		.unwrap()
+		.local_addr()
+		.unwrap()
+		.port()
+}
+
+async fn camera(server: &TestServer, road: u16, mile: u16, plates: &[(&str, u32)]) {
+	let mut client = BinaryClient::connect(server.address()).await;
+
+	let mut frames = vec![ClientFrames::IAmCamera {
+		road,
+		mile,
+		limit: 60,
+	}];
+	for (plate, timestamp) in plates {
+		frames.push(ClientFrames::Plate {
+			plate: plate.to_string(),
+			timestamp: *timestamp,
+		});
+	}
+	frames.push(ClientFrames::WantHeartbeat { interval: 1 });
+
+	for frame in frames {
+		client.send(&frame.to_bytes().unwrap()).await;
+	}
+	client
+		.expect(&ServerFrames::Heartbeat.to_bytes().unwrap())
+		.await;
+}
+
+/// Sends one request to the admin API and returns the status and JSON body.
+async fn request(port: u16, method: &str, target: &str) -> (u16, Value) {
+	let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+		.await
+		.unwrap();
+	stream
+		.write_all(format!("{method} {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+		.await
+		.unwrap();
+
+	let mut response = String::new();
+	stream.read_to_string(&mut response).await.unwrap();
+
+	let (head, body) = response.split_once("\r\n\r\n").unwrap();
+	let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+
+	(status, serde_json::from_str(body).unwrap())
+}
+
+#[tokio::test]
+async fn inspects_and_handles_open_tickets() {
+	let port = free_port();
+	let server = TestServer::start(|listener, shutdown| {
+		let config = Config {
+			admin_port: Some(port),
+			..Config::default()
+		};
+		server::run(listener, config, shutdown)
+	})
+	.await;
+
+	camera(&server, 123, 8, &[("UN1X", 0)]).await;
+	camera(&server, 123, 9, &[("UN1X", 45)]).await;
+
+	let (status, roads) = request(port, "GET", "/roads").await;
+	assert_eq!(status, 200);
+	assert_eq!(roads[0]["road"], 123);
+	assert_eq!(roads[0]["dispatchers"], json!([]));
+	assert_eq!(roads[0]["open_tickets"], 1);
+
+	let ticket = json!({
+		"plate": "UN1X",
+		"road": 123,
+		"mile1": 8,
+		"timestamp1": 0,
+		"mile2": 9,
+		"timestamp2": 45,
+		"speed": 8000,
+	});
+	assert_eq!(
+		request(port, "GET", "/open-tickets").await,
+		(200, json!([ticket]))
+	);
+
+	assert_eq!(
+		request(port, "GET", "/observations?plate=UN1X").await,
+		(
+			200,
+			json!([
+				{ "road": 123, "mile": 9, "timestamp": 45 },
+				{ "road": 123, "mile": 8, "timestamp": 0 },
+			])
+		)
+	);
+	assert_eq!(
+		request(port, "GET", "/observations?plate=UN1X&limit=1")
+			.await
+			.1,
+		json!([{ "road": 123, "mile": 9, "timestamp": 45 }])
+	);
+
+	assert_eq!(
+		request(port, "GET", "/tickets-per-day").await,
+		(200, json!([{ "day": 0, "tickets": 1 }]))
+	);
+
+	// Without a dispatcher for the road the ticket stays open.
+	let target = "/open-tickets/dispatch?road=123&plate=UN1X&timestamp1=0";
+	assert_eq!(request(port, "POST", target).await, (200, ticket.clone()));
+	assert_eq!(
+		request(port, "GET", "/open-tickets").await.1,
+		json!([ticket])
+	);
+
+	let target = "/open-tickets/drop?road=123&plate=UN1X&timestamp1=0";
+	assert_eq!(request(port, "POST", target).await, (200, ticket));
+	assert_eq!(request(port, "GET", "/open-tickets").await.1, json!([]));
+	assert_eq!(request(port, "POST", target).await.0, 404);
+
+	assert_eq!(
+		request(port, "POST", "/open-tickets/drop?road=123").await,
+		(400, json!({ "error": "missing `plate`" }))
+	);
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn counts_a_ticket_on_the_day_of_its_first_observation() {
+	let port = free_port();
+	let server = TestServer::start(|listener, shutdown| {
+		let config = Config {
+			admin_port: Some(port),
+			..Config::default()
+		};
+		server::run(listener, config, shutdown)
+	})
+	.await;
+
+	// 80 mph across midnight, one ticket covering days 0 and 1.
+	camera(&server, 123, 8, &[("NIGHT", 86380)]).await;
+	camera(&server, 123, 9, &[("NIGHT", 86425)]).await;
+
+	assert_eq!(
+		request(port, "GET", "/tickets-per-day").await,
+		(200, json!([{ "day": 0, "tickets": 1 }]))
+	);
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn drops_requests_with_an_oversized_head() {
+	let port = free_port();
+	let server = TestServer::start(|listener, shutdown| {
+		let config = Config {
+			admin_port: Some(port),
+			..Config::default()
+		};
+		server::run(listener, config, shutdown)
+	})
+	.await;
+
+	// The admin API listens once the server answers.
+	camera(&server, 123, 8, &[]).await;
+
+	let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+		.await
+		.unwrap();
+	// A request line that never ends.
+	stream
+		.write_all(format!("GET /{} HTTP/1.1", "a".repeat(9 * 1024)).as_bytes())
+		.await
+		.unwrap();
+
+	let mut response = Vec::new();
+	let closed =
+		tokio::time::timeout(Duration::from_secs(1), stream.read_to_end(&mut response)).await;
+	assert!(closed.is_ok(), "connection still open");
+	assert!(response.is_empty());
+
+	server.stop().await.unwrap();
+}
@@ -0,0 +1,178 @@
+use std::{fs, path::PathBuf, process::Command};
+
+use problem_06::{
+	audit::{self, AuditRecord, Event},
+	server, AuditFormat, ClientFrames, Config, Encode, ServerFrames,
+};
+use protohackers_core::test_support::{BinaryClient, TestServer};
+
+async fn connect(server: &TestServer, frames: &[ClientFrames]) -> BinaryClient {
+	let mut client = BinaryClient::connect(server.address()).await;
+	for frame in frames {
+		client.send(&frame.to_bytes().unwrap()).await;
+	}
+	client
+}
+
+async fn sync(client: &mut BinaryClient) {
+	client
+		.send(
+			&ClientFrames::WantHeartbeat { interval: 1 }
+				.to_bytes()
+				.unwrap(),
+		)
+		.await;
+	client
+		.expect(&ServerFrames::Heartbeat.to_bytes().unwrap())
+		.await;
+}
+
+fn camera(mile: u16, plate: &str, timestamp: u32) -> [ClientFrames; 2] {
+	[
+		ClientFrames::IAmCamera {
+			road: 123,
+			mile,
+			limit: 60,
+		},
+		ClientFrames::Plate {
+			plate: plate.to_string(),
+			timestamp,
+		},
+	]
+}
+
+/// Runs the example session with a dispatcher that connects only after the
+/// ticket was issued and returns the audit log.
+async fn example_session(name: &str, format: AuditFormat) -> (PathBuf, Vec<AuditRecord>) {
+	let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+	let _ = fs::remove_file(&path);
+
+	let config = Config {
+		audit_log: Some(path.clone()),
+		audit_format: format,
+		..Config::default()
+	};
+	let server =
+		TestServer::start(|listener, shutdown| server::run(listener, config, shutdown)).await;
+
+	let mut camera_1 = connect(&server, &camera(8, "UN1X", 0)).await;
+	sync(&mut camera_1).await;
+	let mut camera_2 = connect(&server, &camera(9, "UN1X", 45)).await;
+	sync(&mut camera_2).await;
+
+	let mut dispatcher =
+		connect(&server, &[ClientFrames::IAmDispatcher { roads: vec![123] }]).await;
+	dispatcher.recv(1).await;
+
+	server.stop().await.unwrap();
+
+	let records = audit::read(&path).unwrap();
+	(path, records)
+}
+
+fn assert_example_records(records: &[AuditRecord]) {
+	let events: Vec<_> = records.iter().map(|record| record.event).collect();
+	assert_eq!(events, [Event::Issued, Event::Queued, Event::Dispatched]);
+
+	for record in records {
+		assert_eq!(
+			(
+				record.plate.as_str(),
+				record.road,
+				record.mile1,
+				record.timestamp1,
+				record.mile2,
+				record.timestamp2,
+				record.speed
+			),
+			("UN1X", 123, 8, 0, 9, 45, 8000)
+		);
+	}
+
+	assert!(records[0].camera1.is_some());
+	assert!(records[0].camera2.is_some());
+	assert_ne!(records[0].camera1, records[0].camera2);
+	assert!(records[2].dispatcher.is_some());
+}
+
+#[tokio::test]
+async fn records_tickets_as_json_lines() {
+	let (path, records) = example_session("audit.jsonl", AuditFormat::Jsonl).await;
+	assert_example_records(&records);
+
+	fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn records_the_cameras_that_saw_the_car() {
+	let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("audit_cameras.jsonl");
+	let _ = fs::remove_file(&path);
+
+	let config = Config {
+		audit_log: Some(path.clone()),
+		..Config::default()
+	};
+	let server =
+		TestServer::start(|listener, shutdown| server::run(listener, config, shutdown)).await;
+
+	let mut camera_1 = connect(&server, &camera(8, "UN1X", 0)).await;
+	sync(&mut camera_1).await;
+	// Registers at the same mile after the observation, it did not see the car.
+	let mut other = connect(&server, &camera(8, "RE05BKG", 10)).await;
+	sync(&mut other).await;
+	let mut camera_2 = connect(&server, &camera(9, "UN1X", 45)).await;
+	sync(&mut camera_2).await;
+
+	server.stop().await.unwrap();
+
+	let records = audit::read(&path).unwrap();
+	assert_eq!(records[0].event, Event::Issued);
+	assert_eq!(
+		records[0].camera1,
+		Some(camera_1.local_address().to_string())
+	);
+	assert_eq!(
+		records[0].camera2,
+		Some(camera_2.local_address().to_string())
+	);
+
+	fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn records_tickets_as_csv() {
+	let (path, records) = example_session("audit.csv", AuditFormat::Csv).await;
+	assert_example_records(&records);
+
+	let content = fs::read_to_string(&path).unwrap();
+	assert!(content.starts_with("logged_at,event,plate,road,"));
+
+	fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn queries_the_log_by_plate_road_and_day() {
+	let (path, _) = example_session("audit_query.csv", AuditFormat::Csv).await;
+
+	let query = |args: &[&str]| {
+		let output = Command::new(env!("CARGO_BIN_EXE_speed-daemon-audit"))
+			.arg(&path)
+			.args(args)
+			.output()
+			.unwrap();
+		assert!(output.status.success());
+
+		String::from_utf8(output.stdout).unwrap().lines().count()
+	};
+
+	assert_eq!(query(&[]), 3);
+	assert_eq!(
+		query(&["--plate", "UN1X", "--road", "123", "--day", "0"]),
+		3
+	);
+	assert_eq!(query(&["--plate", "RE05BKG"]), 0);
+	assert_eq!(query(&["--road", "1"]), 0);
+	assert_eq!(query(&["--day", "1"]), 0);
+
+	fs::remove_file(&path).unwrap();
+}
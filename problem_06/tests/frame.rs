@@ -0,0 +1,196 @@
+use bytes::BytesMut;
+use problem_06::frame::{parse_frame, ClientFrames, Encode, FrameError, ServerFrames};
+use proptest::prelude::*;
+
+fn encode(frame: &impl Encode) -> Vec<u8> {
+	frame.to_bytes().unwrap().to_vec()
+}
+
+fn client_frame() -> impl Strategy<Value = ClientFrames> {
+	prop_oneof![
+		("\\PC{0,60}", any::<u32>())
+			.prop_map(|(plate, timestamp)| ClientFrames::Plate { plate, timestamp }),
+		any::<u32>().prop_map(|interval| ClientFrames::WantHeartbeat { interval }),
+		(any::<u16>(), any::<u16>(), any::<u16>())
+			.prop_map(|(road, mile, limit)| ClientFrames::IAmCamera { road, mile, limit }),
+		proptest::collection::vec(any::<u16>(), 0..=255)
+			.prop_map(|roads| ClientFrames::IAmDispatcher { roads }),
+	]
+}
+
+fn server_frame() -> impl Strategy<Value = ServerFrames> {
+	prop_oneof![
+		"\\PC{0,60}".prop_map(|msg| ServerFrames::Error { msg }),
+		(
+			"\\PC{0,60}",
+			any::<u16>(),
+			any::<u16>(),
+			any::<u32>(),
+			any::<u16>(),
+			any::<u32>(),
+			any::<u16>(),
+		)
+			.prop_map(
+				|(plate, road, mile1, timestamp1, mile2, timestamp2, speed)| ServerFrames::Ticket {
+					plate,
+					road,
+					mile1,
+					timestamp1,
+					mile2,
+					timestamp2,
+					speed,
+				}
+			),
+		Just(ServerFrames::Heartbeat),
+	]
+}
+
+/// Feeds `bytes` to the parser in chunks ending at `splits`, like reads from
+/// a socket would. Returns the parsed frames and whether parsing failed.
+fn parse_chunked(bytes: &[u8], splits: &[usize]) -> (Vec<ClientFrames>, bool) {
+	let mut ends: Vec<usize> = splits
+		.iter()
+		.map(|split| split % (bytes.len() + 1))
+		.collect();
+	ends.push(bytes.len());
+	ends.sort_unstable();
+
+	let mut buffer = BytesMut::new();
+	let mut frames = Vec::new();
+	let mut start = 0;
+
+	for end in ends {
+		buffer.extend_from_slice(&bytes[start..end]);
+		start = end;
+
+		loop {
+			match parse_frame(&mut buffer) {
+				Ok(Some(frame)) => frames.push(frame),
+				Ok(None) => break,
+				Err(_) => return (frames, true),
+			}
+		}
+	}
+
+	(frames, false)
+}
+
+fn parse_client_frame(bytes: &[u8]) -> Result<Option<ClientFrames>, FrameError> {
+	parse_frame(&mut BytesMut::from(bytes))
+}
+
+#[test]
+fn rejects_unknown_frame_types() {
+	assert_eq!(
+		parse_client_frame(&[0xff]),
+		Err(FrameError::UnknownType(0xff))
+	);
+	// Server frames are unknown in the client direction.
+	assert_eq!(
+		parse_client_frame(&[0x41]),
+		Err(FrameError::UnknownType(0x41))
+	);
+	assert_eq!(
+		parse_frame::<ServerFrames>(&mut BytesMut::from(&[0x20][..])),
+		Err(FrameError::UnknownType(0x20))
+	);
+}
+
+#[test]
+fn rejects_plates_that_are_not_utf8() {
+	let frame = [0x20, 0x02, 0xc3, 0x28, 0x00, 0x00, 0x00, 0x00];
+
+	assert_eq!(parse_client_frame(&frame), Err(FrameError::InvalidUtf8));
+}
+
+#[test]
+fn waits_for_all_roads_of_a_dispatcher() {
+	let mut frame = vec![0x81, 200];
+	frame.extend(std::iter::repeat_n(0x00, 399));
+	assert_eq!(parse_client_frame(&frame), Ok(None));
+
+	frame.push(0x07);
+	let Ok(Some(ClientFrames::IAmDispatcher { roads })) = parse_client_frame(&frame) else {
+		panic!("expected a dispatcher");
+	};
+	assert_eq!(roads.len(), 200);
+	assert_eq!(roads.last(), Some(&7));
+}
+
+#[test]
+fn refuses_to_encode_strings_longer_than_255_bytes() {
+	let mut buf = BytesMut::new();
+	let frame = ClientFrames::Plate {
+		plate: "A".repeat(256),
+		timestamp: 0,
+	};
+
+	assert_eq!(frame.encode(&mut buf), Err(FrameError::TooLong(256)));
+	assert!(buf.is_empty());
+
+	let frame = ClientFrames::IAmDispatcher {
+		roads: vec![1; 256],
+	};
+	assert_eq!(frame.encode(&mut buf), Err(FrameError::TooLong(256)));
+	assert!(buf.is_empty());
+}
+
+proptest! {
+	#[test]
+	fn round_trips_every_client_frame(frame in client_frame()) {
+		let mut buffer = BytesMut::from(&encode(&frame)[..]);
+
+		prop_assert_eq!(parse_frame(&mut buffer).unwrap(), Some(frame));
+		prop_assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn round_trips_every_server_frame(frame in server_frame()) {
+		let mut buffer = BytesMut::from(&encode(&frame)[..]);
+
+		prop_assert_eq!(parse_frame(&mut buffer).unwrap(), Some(frame));
+		prop_assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn waits_for_the_rest_of_a_partial_server_frame(frame in server_frame()) {
+		let bytes = encode(&frame);
+
+		for end in 0..bytes.len() {
+			let mut buffer = BytesMut::from(&bytes[..end]);
+
+			prop_assert_eq!(parse_frame::<ServerFrames>(&mut buffer).unwrap(), None);
+			prop_assert_eq!(&buffer[..], &bytes[..end]);
+		}
+	}
+
+	#[test]
+	fn waits_for_the_rest_of_a_partial_client_frame(frame in client_frame()) {
+		let bytes = encode(&frame);
+
+		for end in 0..bytes.len() {
+			let mut buffer = BytesMut::from(&bytes[..end]);
+
+			prop_assert_eq!(parse_frame::<ClientFrames>(&mut buffer).unwrap(), None);
+			prop_assert_eq!(&buffer[..], &bytes[..end]);
+		}
+	}
+
+	#[test]
+	fn parses_streams_split_at_any_point(
+		frames in proptest::collection::vec(client_frame(), 0..8),
+		splits in proptest::collection::vec(any::<usize>(), 0..16),
+	) {
+		let bytes: Vec<u8> = frames.iter().flat_map(encode).collect();
+
+		prop_assert_eq!(parse_chunked(&bytes, &splits), (frames, false));
+	}
+
+	#[test]
+	fn parses_arbitrary_bytes_the_same_however_they_are_split(
+		bytes in proptest::collection::vec(any::<u8>(), 0..512),
+		splits in proptest::collection::vec(any::<usize>(), 0..16),
+	) {
+		prop_assert_eq!(parse_chunked(&bytes, &splits), parse_chunked(&bytes, &[]));
+	}
+}
@@ -0,0 +1,27 @@
+use problem_06::{server, Config};
+use protohackers_core::test_support::TestServer;
+use tokio::process::Command;
+
+#[tokio::test]
+async fn load_generator_gets_every_expected_ticket() {
+	let server =
+		TestServer::start(|listener, shutdown| server::run(listener, Config::default(), shutdown))
+			.await;
+
+	let output = Command::new(env!("CARGO_BIN_EXE_speed-daemon-load"))
+		.args(["--address", &server.address().to_string()])
+		.args(["--roads", "20", "--cameras", "4", "--cars", "10"])
+		.args(["--dispatchers", "3", "--timeout", "10"])
+		.output()
+		.await
+		.unwrap();
+
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	assert!(output.status.success(), "{stdout}");
+	assert!(
+		stdout.contains("0 missing, 0 duplicated, 0 unexpected"),
+		"{stdout}"
+	);
+
+	server.stop().await.unwrap();
+}
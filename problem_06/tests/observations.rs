@@ -0,0 +1,72 @@
+use problem_06::{
+	observations::{Neighbours, Observation, Timeline},
+	policy::TicketPolicy,
+};
+
+fn at(mile: u16, timestamp: u32) -> Observation {
+	Observation { mile, timestamp }
+}
+
+#[test]
+fn the_first_observation_has_no_neighbours() {
+	let mut timeline = Timeline::default();
+
+	assert_eq!(timeline.insert(5, 100, None), Neighbours::default());
+}
+
+#[test]
+fn checks_an_out_of_order_observation_against_both_neighbours() {
+	let policy = TicketPolicy::new(60);
+	let mut timeline = Timeline::default();
+	timeline.insert(0, 0, None);
+	timeline.insert(20, 1000, None);
+
+	// Arrives last but sits between the two in time.
+	let neighbours = timeline.insert(10, 900, None);
+	assert_eq!(
+		neighbours,
+		Neighbours {
+			before: Some(at(0, 0)),
+			after: Some(at(20, 1000)),
+		}
+	);
+
+	// 40 mph since the predecessor, 360 mph until the successor.
+	let tickets: Vec<_> = neighbours
+		.iter()
+		.filter_map(|neighbour| policy.check(at(10, 900), neighbour))
+		.map(|speeding| (speeding.first, speeding.second))
+		.collect();
+	assert_eq!(tickets, [(at(10, 900), at(20, 1000))]);
+}
+
+#[test]
+fn ignores_a_second_observation_at_the_same_time() {
+	let mut timeline = Timeline::default();
+	timeline.insert(0, 0, None);
+	timeline.insert(10, 100, None);
+
+	assert_eq!(timeline.insert(20, 100, None), Neighbours::default());
+	assert_eq!(timeline.mile_at(100), Some(10));
+	assert_eq!(timeline.iter().collect::<Vec<_>>(), [at(0, 0), at(10, 100)]);
+}
+
+#[test]
+fn never_tickets_a_plate_seen_at_only_one_mile() {
+	let policy = TicketPolicy::new(60);
+	let mut timeline = Timeline::default();
+	timeline.insert(7, 0, None);
+	timeline.insert(7, 200, None);
+
+	let neighbours = timeline.insert(7, 100, None);
+	assert_eq!(
+		neighbours,
+		Neighbours {
+			before: Some(at(7, 0)),
+			after: Some(at(7, 200)),
+		}
+	);
+	assert!(neighbours
+		.iter()
+		.all(|neighbour| policy.check(at(7, 100), neighbour).is_none()));
+}
@@ -0,0 +1,751 @@
+use std::{
+	fs,
+	net::{Ipv4Addr, TcpListener},
+	path::PathBuf,
+};
+
+use bytes::BytesMut;
+use problem_06::{
+	frame::parse_frame, server, ClientFrames, Config, Dispatch, Encode, ServerFrames,
+};
+use protohackers_core::test_support::{BinaryClient, TestServer};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::{TcpSocket, TcpStream},
+	task::JoinSet,
+	time::Duration,
+};
+
+async fn start() -> TestServer {
+	start_with(Config::default()).await
+}
+
+async fn start_with(config: Config) -> TestServer {
+	TestServer::start(|listener, shutdown| server::run(listener, config, shutdown)).await
+}
+
+/// A port nothing listens on right now.
+fn free_port() -> u16 {
+	TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+		.unwrap()
+		.local_addr()
+		.unwrap()
+		.port()
+}
+
+/// The metrics the server exposes on `port`.
+async fn metrics(port: u16) -> String {
+	let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+		.await
+		.unwrap();
+	stream
+		.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+		.await
+		.unwrap();
+
+	let mut response = String::new();
+	stream.read_to_string(&mut response).await.unwrap();
+	response
+}
+
+async fn connect(server: &TestServer, frame: ClientFrames) -> BinaryClient {
+	let mut client = BinaryClient::connect(server.address()).await;
+	send(&mut client, frame).await;
+	client
+}
+
+async fn send(client: &mut BinaryClient, frame: ClientFrames) {
+	client.send(&frame.to_bytes().unwrap()).await;
+}
+
+async fn expect(client: &mut BinaryClient, frame: ServerFrames) {
+	client.expect(&frame.to_bytes().unwrap()).await;
+}
+
+/// Like [`expect`], for clients that asked for heartbeats.
+async fn expect_between_heartbeats(client: &mut BinaryClient, frame: ServerFrames) {
+	let bytes = frame.to_bytes().unwrap();
+
+	loop {
+		let frame_type = client.recv(1).await[0];
+		if frame_type != 0x41 {
+			assert_eq!(frame_type, bytes[0]);
+			client.expect(&bytes[1..]).await;
+			return;
+		}
+	}
+}
+
+/// Receives the next frame, whatever it is.
+async fn recv_frame(client: &mut BinaryClient) -> ServerFrames {
+	let mut buffer = BytesMut::new();
+	loop {
+		buffer.extend_from_slice(&client.recv(1).await);
+		if let Some(frame) = parse_frame::<ServerFrames>(&mut buffer).unwrap() {
+			return frame;
+		}
+	}
+}
+
+/// Waits until the server handled everything `client` sent so far, frames of
+/// one connection are handled in order. Works once per connection, as a
+/// client may only ask for heartbeats once.
+async fn sync(client: &mut BinaryClient) {
+	send(client, ClientFrames::WantHeartbeat { interval: 1 }).await;
+	expect(client, ServerFrames::Heartbeat).await;
+}
+
+fn camera(road: u16, mile: u16, limit: u16) -> ClientFrames {
+	ClientFrames::IAmCamera { road, mile, limit }
+}
+
+fn dispatcher(roads: &[u16]) -> ClientFrames {
+	ClientFrames::IAmDispatcher {
+		roads: roads.to_vec(),
+	}
+}
+
+fn plate(plate: &str, timestamp: u32) -> ClientFrames {
+	ClientFrames::Plate {
+		plate: plate.to_string(),
+		timestamp,
+	}
+}
+
+fn error(msg: &str) -> ServerFrames {
+	ServerFrames::Error {
+		msg: msg.to_string(),
+	}
+}
+
+fn ticket(
+	plate: &str,
+	road: u16,
+	(mile1, timestamp1): (u16, u32),
+	(mile2, timestamp2): (u16, u32),
+	speed: u16,
+) -> ServerFrames {
+	ServerFrames::Ticket {
+		plate: plate.to_string(),
+		road,
+		mile1,
+		timestamp1,
+		mile2,
+		timestamp2,
+		speed,
+	}
+}
+
+#[tokio::test]
+async fn tickets_the_car_of_the_example_session() {
+	let server = start().await;
+
+	let mut camera_1 = connect(&server, camera(123, 8, 60)).await;
+	send(&mut camera_1, plate("UN1X", 0)).await;
+
+	let mut camera_2 = connect(&server, camera(123, 9, 60)).await;
+	send(&mut camera_2, plate("UN1X", 45)).await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[123])).await;
+	expect(&mut dispatcher, ticket("UN1X", 123, (8, 0), (9, 45), 8000)).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn delivers_tickets_to_a_connected_dispatcher() {
+	let server = start().await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[66, 42])).await;
+
+	let mut camera_1 = connect(&server, camera(42, 100, 50)).await;
+	send(&mut camera_1, plate("RE05BKG", 100_000)).await;
+
+	let mut camera_2 = connect(&server, camera(42, 110, 50)).await;
+	send(&mut camera_2, plate("RE05BKG", 100_600)).await;
+
+	let ticket = ticket("RE05BKG", 42, (100, 100_000), (110, 100_600), 6000);
+	expect(&mut dispatcher, ticket).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn does_not_ticket_cars_within_the_limit() {
+	let server = start().await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[7])).await;
+
+	let mut camera_1 = connect(&server, camera(7, 0, 60)).await;
+	send(&mut camera_1, plate("SLOW", 0)).await;
+
+	let mut camera_2 = connect(&server, camera(7, 1, 60)).await;
+	send(&mut camera_2, plate("SLOW", 60)).await;
+
+	dispatcher.expect_silence(Duration::from_millis(300)).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn sends_heartbeats_at_the_requested_interval() {
+	let server = start().await;
+
+	let mut client = connect(&server, ClientFrames::WantHeartbeat { interval: 1 }).await;
+	expect(&mut client, ServerFrames::Heartbeat).await;
+	expect(&mut client, ServerFrames::Heartbeat).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn answers_a_second_heartbeat_request_with_an_error_and_disconnects() {
+	let server = start().await;
+
+	let mut client = connect(&server, ClientFrames::WantHeartbeat { interval: 0 }).await;
+	send(&mut client, ClientFrames::WantHeartbeat { interval: 10 }).await;
+	expect(&mut client, error("Heartbeat already requested")).await;
+	client.expect_closed().await;
+
+	let mut client = connect(&server, ClientFrames::WantHeartbeat { interval: 1 }).await;
+	expect(&mut client, ServerFrames::Heartbeat).await;
+	send(&mut client, ClientFrames::WantHeartbeat { interval: 1 }).await;
+	expect_between_heartbeats(&mut client, error("Heartbeat already requested")).await;
+	client.expect_closed().await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn answers_plates_from_non_cameras_with_an_error_and_disconnects() {
+	let server = start().await;
+
+	let mut client = connect(&server, plate("UN1X", 0)).await;
+	expect(&mut client, error("Not connected as camera")).await;
+	client.expect_closed().await;
+
+	let mut client = connect(&server, dispatcher(&[1])).await;
+	send(&mut client, plate("UN1X", 0)).await;
+	expect(&mut client, error("Not connected as camera")).await;
+	client.expect_closed().await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn answers_a_second_identification_with_an_error_and_disconnects() {
+	let server = start().await;
+
+	let mut client = connect(&server, camera(1, 1, 60)).await;
+	send(&mut client, dispatcher(&[1])).await;
+	expect(&mut client, error("Already connected as a connection type")).await;
+	client.expect_closed().await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn answers_unknown_frames_with_an_error_and_disconnects() {
+	let server = start().await;
+	let mut client = BinaryClient::connect(server.address()).await;
+
+	client.send(&[0xff]).await;
+	expect(&mut client, error("unknown frame type 0xff")).await;
+	client.expect_closed().await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn answers_malformed_plates_with_an_error_and_disconnects() {
+	let server = start().await;
+
+	let mut client = connect(&server, camera(1, 1, 60)).await;
+	client
+		.send(&[0x20, 0x02, 0xc3, 0x28, 0x00, 0x00, 0x00, 0x00])
+		.await;
+	expect(&mut client, error("string is not valid UTF-8")).await;
+	client.expect_closed().await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn disconnects_clients_that_do_not_finish_a_frame_in_time() {
+	let server = start_with(Config {
+		frame_timeout_secs: 1,
+		..Config::default()
+	})
+	.await;
+
+	let mut client = connect(&server, camera(1, 1, 60)).await;
+	client.send(&[0x20, 0x04, b'U', b'N']).await;
+	expect(&mut client, error("frame not finished in time")).await;
+	client.expect_closed().await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn disconnects_dispatchers_that_fall_too_far_behind() {
+	let server = start_with(Config {
+		outbound_queue: 1,
+		dispatch: Dispatch::First,
+		..Config::default()
+	})
+	.await;
+
+	let mut camera_1 = connect(&server, camera(1, 0, 60)).await;
+	for name in ["WARMUP", "CAR1", "CAR2"] {
+		send(&mut camera_1, plate(name, 0)).await;
+	}
+	sync(&mut camera_1).await;
+
+	// The first dispatcher registers before the second, which then gets
+	// the tickets the first cannot take.
+	let mut dispatcher_1 = connect(&server, dispatcher(&[1])).await;
+	let mut camera_2 = connect(&server, camera(1, 1, 60)).await;
+	send(&mut camera_2, plate("WARMUP", 30)).await;
+	expect(
+		&mut dispatcher_1,
+		ticket("WARMUP", 1, (0, 0), (1, 30), 12000),
+	)
+	.await;
+
+	let mut dispatcher_2 = connect(&server, dispatcher(&[1, 2])).await;
+	let mut camera_3 = connect(&server, camera(2, 0, 60)).await;
+	send(&mut camera_3, plate("WARMUP2", 0)).await;
+	let mut camera_4 = connect(&server, camera(2, 1, 60)).await;
+	send(&mut camera_4, plate("WARMUP2", 30)).await;
+	expect(
+		&mut dispatcher_2,
+		ticket("WARMUP2", 2, (0, 0), (1, 30), 12000),
+	)
+	.await;
+
+	// Both tickets are issued before the first dispatcher takes one off its
+	// queue, the second does not fit.
+	let mut camera_5 = connect(&server, camera(1, 2, 60)).await;
+	let mut frames = plate("CAR1", 30).to_bytes().unwrap();
+	frames.extend(plate("CAR2", 30).to_bytes().unwrap());
+	camera_5.send(&frames).await;
+
+	// The first dispatcher writes the ticket it took before it goes.
+	expect(&mut dispatcher_1, ticket("CAR1", 1, (0, 0), (2, 30), 24000)).await;
+	expect(&mut dispatcher_1, error("too far behind")).await;
+	dispatcher_1.expect_closed().await;
+
+	expect(&mut dispatcher_2, ticket("CAR2", 1, (0, 0), (2, 30), 24000)).await;
+	dispatcher_2
+		.expect_silence(Duration::from_millis(300))
+		.await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn hands_the_tickets_of_a_dispatcher_that_does_not_read_to_another() {
+	let server = start_with(Config {
+		write_timeout_secs: 1,
+		outbound_queue: 100_000,
+		..Config::default()
+	})
+	.await;
+
+	// Fills up quickly as it never reads.
+	let socket = TcpSocket::new_v4().unwrap();
+	socket.set_recv_buffer_size(4096).unwrap();
+	let mut stuck = socket.connect(server.address()).await.unwrap();
+	stuck
+		.write_all(&dispatcher(&[1]).to_bytes().unwrap())
+		.await
+		.unwrap();
+
+	// Long plates make for long tickets.
+	let plates: Vec<_> = (0..20_000).map(|i| format!("{i:0250}")).collect();
+	for (mile, timestamp) in [(0, 0), (1, 60)] {
+		let mut camera = connect(&server, camera(1, mile, 10)).await;
+		let frames: Vec<u8> = plates
+			.iter()
+			.flat_map(|name| plate(name, timestamp).to_bytes().unwrap())
+			.collect();
+		camera.send(&frames).await;
+		sync(&mut camera).await;
+	}
+
+	// Once the write timed out, the tickets the stuck dispatcher did not
+	// write are passed on.
+	let mut dispatcher = connect(&server, dispatcher(&[1])).await;
+	let frame = recv_frame(&mut dispatcher).await;
+	assert!(
+		matches!(frame, ServerFrames::Ticket { speed: 6000, .. }),
+		"unexpected {frame:?}"
+	);
+
+	drop(stuck);
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn disconnects_idle_clients_only_with_an_idle_timeout() {
+	let server = start_with(Config {
+		idle_timeout_secs: Some(1),
+		..Config::default()
+	})
+	.await;
+
+	let mut client = connect(&server, camera(1, 1, 60)).await;
+	expect(&mut client, error("idle for too long")).await;
+	client.expect_closed().await;
+
+	server.stop().await.unwrap();
+
+	let server = start().await;
+
+	let mut client = connect(&server, camera(1, 1, 60)).await;
+	client.expect_silence(Duration::from_millis(1500)).await;
+	send(&mut client, plate("UN1X", 0)).await;
+	sync(&mut client).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn restores_its_state_from_the_journal_after_a_restart() {
+	let journal = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("speed_daemon_journal.jsonl");
+	let _ = fs::remove_file(&journal);
+	let config = Config {
+		journal: Some(journal.clone()),
+		..Config::default()
+	};
+
+	let server = start_with(config.clone()).await;
+
+	let mut camera_1 = connect(&server, camera(123, 8, 60)).await;
+	send(&mut camera_1, plate("UN1X", 0)).await;
+	send(&mut camera_1, plate("RE05BKG", 0)).await;
+	sync(&mut camera_1).await;
+
+	let mut camera_2 = connect(&server, camera(123, 9, 60)).await;
+	send(&mut camera_2, plate("UN1X", 45)).await;
+	sync(&mut camera_2).await;
+
+	server.stop().await.unwrap();
+	let server = start_with(config.clone()).await;
+
+	// The ticket nobody could be sent before the restart is still pending.
+	let mut dispatcher = connect(&server, dispatcher(&[123])).await;
+	expect(&mut dispatcher, ticket("UN1X", 123, (8, 0), (9, 45), 8000)).await;
+
+	// Observations from before the restart still count.
+	let mut camera_3 = connect(&server, camera(123, 10, 60)).await;
+	send(&mut camera_3, plate("RE05BKG", 90)).await;
+	expect(
+		&mut dispatcher,
+		ticket("RE05BKG", 123, (8, 0), (10, 90), 8000),
+	)
+	.await;
+
+	// UN1X was already ticketed for this day.
+	send(&mut camera_3, plate("UN1X", 90)).await;
+	dispatcher.expect_silence(Duration::from_millis(300)).await;
+
+	server.stop().await.unwrap();
+	let server = start_with(config).await;
+
+	// Delivered tickets are not sent again.
+	let mut reconnected = connect(&server, self::dispatcher(&[123])).await;
+	reconnected.expect_silence(Duration::from_millis(300)).await;
+
+	server.stop().await.unwrap();
+	fs::remove_file(&journal).unwrap();
+}
+
+#[tokio::test]
+async fn keeps_tickets_for_a_disconnected_dispatcher() {
+	let server = start().await;
+
+	let mut gone = connect(&server, dispatcher(&[5])).await;
+	gone.close().await;
+	gone.expect_closed().await;
+
+	let mut camera_1 = connect(&server, camera(5, 0, 30)).await;
+	send(&mut camera_1, plate("GONE", 0)).await;
+	sync(&mut camera_1).await;
+	let mut camera_2 = connect(&server, camera(5, 1, 30)).await;
+	send(&mut camera_2, plate("GONE", 60)).await;
+	sync(&mut camera_2).await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[5])).await;
+	expect(&mut dispatcher, ticket("GONE", 5, (0, 0), (1, 60), 6000)).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn fails_over_to_the_next_dispatcher_of_the_road() {
+	let server = start().await;
+
+	let mut first = connect(&server, dispatcher(&[5])).await;
+	let mut second = connect(&server, dispatcher(&[5, 6])).await;
+	sync(&mut second).await;
+
+	first.close().await;
+	first.expect_closed().await;
+
+	let mut camera_1 = connect(&server, camera(5, 0, 30)).await;
+	send(&mut camera_1, plate("NEXT", 0)).await;
+	sync(&mut camera_1).await;
+	let mut camera_2 = connect(&server, camera(5, 1, 30)).await;
+	send(&mut camera_2, plate("NEXT", 60)).await;
+
+	let ticket = ticket("NEXT", 5, (0, 0), (1, 60), 6000);
+	expect_between_heartbeats(&mut second, ticket).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn takes_turns_between_dispatchers_with_round_robin() {
+	let server = start_with(Config {
+		dispatch: Dispatch::RoundRobin,
+		..Config::default()
+	})
+	.await;
+
+	let mut first = connect(&server, dispatcher(&[9])).await;
+	sync(&mut first).await;
+	let mut second = connect(&server, dispatcher(&[9])).await;
+	sync(&mut second).await;
+
+	let mut camera_1 = connect(&server, camera(9, 0, 30)).await;
+	send(&mut camera_1, plate("ONE", 0)).await;
+	send(&mut camera_1, plate("TWO", 0)).await;
+	sync(&mut camera_1).await;
+	let mut camera_2 = connect(&server, camera(9, 1, 30)).await;
+	send(&mut camera_2, plate("ONE", 60)).await;
+	send(&mut camera_2, plate("TWO", 60)).await;
+
+	let ticket_1 = ticket("ONE", 9, (0, 0), (1, 60), 6000);
+	expect_between_heartbeats(&mut first, ticket_1).await;
+	let ticket_2 = ticket("TWO", 9, (0, 0), (1, 60), 6000);
+	expect_between_heartbeats(&mut second, ticket_2).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn tickets_cars_on_many_roads_at_once() {
+	let server = start().await;
+	let roads: Vec<u16> = (1..=20).collect();
+
+	let mut dispatcher = connect(&server, dispatcher(&roads)).await;
+	sync(&mut dispatcher).await;
+
+	let mut cameras = JoinSet::new();
+	for &road in &roads {
+		let address = server.address();
+		cameras.spawn(async move {
+			let plate_name = format!("CAR{road:02}");
+			for (mile, timestamp) in [(0, 0), (1, 60)] {
+				let mut client = BinaryClient::connect(address).await;
+				send(&mut client, camera(road, mile, 30)).await;
+				send(&mut client, plate(&plate_name, timestamp)).await;
+				sync(&mut client).await;
+			}
+		});
+	}
+	cameras.join_all().await;
+
+	let mut expected: Vec<_> = roads
+		.iter()
+		.map(|&road| ticket(&format!("CAR{road:02}"), road, (0, 0), (1, 60), 6000))
+		.collect();
+
+	let mut buffer = BytesMut::new();
+	while !expected.is_empty() {
+		buffer.extend_from_slice(&dispatcher.recv(1).await);
+		if let Some(frame) = parse_frame::<ServerFrames>(&mut buffer).unwrap() {
+			if frame != ServerFrames::Heartbeat {
+				let position = expected.iter().position(|ticket| *ticket == frame);
+				expected.swap_remove(position.unwrap_or_else(|| panic!("unexpected {frame:?}")));
+			}
+		}
+	}
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn forgets_observations_outside_the_retention() {
+	let server = start_with(Config {
+		retention_days: Some(1),
+		compaction_interval_secs: 1,
+		..Config::default()
+	})
+	.await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[42])).await;
+
+	let mut camera_1 = connect(&server, camera(42, 0, 60)).await;
+	send(&mut camera_1, plate("OLD1", 0)).await;
+	// Another car moves the latest observation on the road three days on.
+	send(&mut camera_1, plate("NEW1", 3 * 86400)).await;
+	sync(&mut camera_1).await;
+
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+
+	// Would be 600 mph against the dropped observation at timestamp 0.
+	let mut camera_2 = connect(&server, camera(42, 10, 60)).await;
+	send(&mut camera_2, plate("OLD1", 60)).await;
+	sync(&mut camera_2).await;
+
+	dispatcher.expect_silence(Duration::from_millis(300)).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn does_not_ticket_a_car_twice_for_a_compacted_day() {
+	let server = start_with(Config {
+		retention_days: Some(1),
+		compaction_interval_secs: 1,
+		..Config::default()
+	})
+	.await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[42])).await;
+
+	let mut camera_1 = connect(&server, camera(42, 0, 60)).await;
+	send(&mut camera_1, plate("X", 0)).await;
+	sync(&mut camera_1).await;
+	let mut camera_2 = connect(&server, camera(42, 10, 60)).await;
+	send(&mut camera_2, plate("X", 60)).await;
+	sync(&mut camera_2).await;
+
+	expect(&mut dispatcher, ticket("X", 42, (0, 0), (10, 60), 60000)).await;
+
+	send(&mut camera_1, plate("NEW1", 3 * 86400)).await;
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+
+	// Day 0 was dropped along with its ticket, the car must not get another.
+	let mut camera_3 = connect(&server, camera(42, 20, 60)).await;
+	send(&mut camera_3, plate("X", 120)).await;
+	sync(&mut camera_3).await;
+	let mut camera_4 = connect(&server, camera(42, 30, 60)).await;
+	send(&mut camera_4, plate("X", 180)).await;
+	sync(&mut camera_4).await;
+
+	dispatcher.expect_silence(Duration::from_millis(300)).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn compacts_the_journal_along_with_the_state() {
+	let journal = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("speed_daemon_compacted.jsonl");
+	let _ = fs::remove_file(&journal);
+	let config = Config {
+		journal: Some(journal.clone()),
+		retention_days: Some(1),
+		compaction_interval_secs: 1,
+		..Config::default()
+	};
+
+	let server = start_with(config.clone()).await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[42])).await;
+
+	let mut camera_1 = connect(&server, camera(42, 0, 60)).await;
+	send(&mut camera_1, plate("X", 0)).await;
+	sync(&mut camera_1).await;
+	let mut camera_2 = connect(&server, camera(42, 10, 60)).await;
+	send(&mut camera_2, plate("X", 60)).await;
+	sync(&mut camera_2).await;
+
+	expect(&mut dispatcher, ticket("X", 42, (0, 0), (10, 60), 60000)).await;
+
+	send(&mut camera_1, plate("NEW1", 3 * 86400)).await;
+	sync(&mut camera_1).await;
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+
+	server.stop().await.unwrap();
+
+	// The cutoff of road 42, the first kept day and the observation of NEW1.
+	let lines = fs::read_to_string(&journal).unwrap().lines().count();
+	assert_eq!(lines, 3);
+
+	let server = start_with(config).await;
+
+	let mut dispatcher = connect(&server, self::dispatcher(&[42])).await;
+
+	// The observations of day 0 stay dropped after the restart.
+	let mut camera_3 = connect(&server, camera(42, 20, 60)).await;
+	send(&mut camera_3, plate("X", 120)).await;
+	sync(&mut camera_3).await;
+	let mut camera_4 = connect(&server, camera(42, 30, 60)).await;
+	send(&mut camera_4, plate("X", 180)).await;
+	sync(&mut camera_4).await;
+
+	dispatcher.expect_silence(Duration::from_millis(300)).await;
+
+	// The kept observation still counts.
+	let mut camera_5 = connect(&server, camera(42, 10, 60)).await;
+	send(&mut camera_5, plate("NEW1", 3 * 86400 + 60)).await;
+	expect(
+		&mut dispatcher,
+		ticket("NEW1", 42, (0, 3 * 86400), (10, 3 * 86400 + 60), 60000),
+	)
+	.await;
+
+	server.stop().await.unwrap();
+	fs::remove_file(&journal).unwrap();
+}
+
+#[tokio::test]
+async fn ignores_duplicate_observations() {
+	let server = start().await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[8])).await;
+
+	let mut camera_1 = connect(&server, camera(8, 0, 60)).await;
+	send(&mut camera_1, plate("TWICE", 0)).await;
+	send(&mut camera_1, plate("TWICE", 0)).await;
+	sync(&mut camera_1).await;
+
+	let mut camera_2 = connect(&server, camera(8, 1, 60)).await;
+	send(&mut camera_2, plate("TWICE", 45)).await;
+	send(&mut camera_2, plate("TWICE", 45)).await;
+	sync(&mut camera_2).await;
+
+	expect(&mut dispatcher, ticket("TWICE", 8, (0, 0), (1, 45), 8000)).await;
+	dispatcher.expect_silence(Duration::from_millis(300)).await;
+
+	server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn keeps_serving_cameras_with_conflicting_limits() {
+	let mut config = Config::default();
+	let port = free_port();
+	config.server.metrics_port = Some(port);
+	let server = start_with(config).await;
+
+	let mut camera_1 = connect(&server, camera(9, 0, 60)).await;
+	sync(&mut camera_1).await;
+	let mut camera_2 = connect(&server, camera(9, 1, 70)).await;
+	sync(&mut camera_2).await;
+
+	send(&mut camera_1, plate("FAST", 0)).await;
+	send(&mut camera_2, plate("FAST", 45)).await;
+
+	let mut dispatcher = connect(&server, dispatcher(&[9])).await;
+	expect(&mut dispatcher, ticket("FAST", 9, (0, 0), (1, 45), 8000)).await;
+
+	assert!(metrics(port)
+		.await
+		.lines()
+		.any(|line| line == "speed_daemon_conflicting_limits_total 1"));
+
+	server.stop().await.unwrap();
+}
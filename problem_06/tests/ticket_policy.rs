@@ -0,0 +1,154 @@
+use problem_06::{
+	observations::Observation,
+	policy::{Speeding, TicketPolicy, SECONDS_PER_DAY},
+};
+
+fn at(mile: u16, timestamp: u32) -> Observation {
+	Observation { mile, timestamp }
+}
+
+fn speeding(first: Observation, second: Observation, speed: u16) -> Option<Speeding> {
+	Some(Speeding {
+		first,
+		second,
+		speed,
+		days: first.timestamp / SECONDS_PER_DAY..=second.timestamp / SECONDS_PER_DAY,
+	})
+}
+
+#[test]
+fn tickets_the_car_of_the_example_session() {
+	let policy = TicketPolicy::new(60);
+
+	assert_eq!(
+		policy.check(at(8, 0), at(9, 45)),
+		speeding(at(8, 0), at(9, 45), 8000)
+	);
+}
+
+#[test]
+fn orders_the_observations_by_time() {
+	let policy = TicketPolicy::new(60);
+
+	assert_eq!(
+		policy.check(at(9, 45), at(8, 0)),
+		speeding(at(8, 0), at(9, 45), 8000)
+	);
+}
+
+#[test]
+fn does_not_ticket_a_car_at_exactly_the_limit() {
+	let policy = TicketPolicy::new(60);
+
+	assert_eq!(policy.check(at(0, 0), at(1, 60)), None);
+	assert_eq!(policy.check(at(0, 0), at(0, 60)), None);
+}
+
+#[test]
+fn does_not_ticket_a_car_less_than_half_a_mile_per_hour_too_fast() {
+	// 3 miles in 179 seconds are 60.34 mph, 300 miles in 17928 seconds 60.24 mph.
+	let policy = TicketPolicy::new(60);
+
+	assert_eq!(policy.check(at(0, 0), at(3, 179)), None);
+	assert_eq!(policy.check(at(0, 0), at(300, 17_928)), None);
+}
+
+#[test]
+fn tickets_a_car_half_a_mile_per_hour_too_fast() {
+	// 121 miles in 7200 seconds is exactly 60.5 mph.
+	let policy = TicketPolicy::new(60);
+
+	assert_eq!(
+		policy.check(at(0, 0), at(121, 7200)),
+		speeding(at(0, 0), at(121, 7200), 6050)
+	);
+}
+
+#[test]
+fn rounds_the_speed_to_the_nearest_hundredth() {
+	// 1 mile in 7 seconds is 514.2857... mph.
+	let policy = TicketPolicy::new(60);
+	assert_eq!(
+		policy.check(at(0, 0), at(1, 7)),
+		speeding(at(0, 0), at(1, 7), 51429)
+	);
+
+	// 1 mile in 70 seconds is 51.428... mph.
+	let policy = TicketPolicy::new(50);
+	assert_eq!(
+		policy.check(at(0, 0), at(1, 70)),
+		speeding(at(0, 0), at(1, 70), 5143)
+	);
+}
+
+#[test]
+fn ignores_observations_at_the_same_time() {
+	let policy = TicketPolicy::new(60);
+
+	assert_eq!(policy.check(at(0, 100), at(10, 100)), None);
+	assert_eq!(policy.check(at(10, 100), at(10, 100)), None);
+}
+
+#[test]
+fn caps_speeds_that_do_not_fit() {
+	let policy = TicketPolicy::new(60);
+
+	assert_eq!(
+		policy.check(at(0, 0), at(u16::MAX, 1)),
+		speeding(at(0, 0), at(u16::MAX, 1), u16::MAX)
+	);
+}
+
+#[test]
+fn tickets_any_speed_on_a_road_with_limit_zero() {
+	let policy = TicketPolicy::new(0);
+
+	assert_eq!(
+		policy.check(at(0, 0), at(1, 3600)),
+		speeding(at(0, 0), at(1, 3600), 100)
+	);
+	assert_eq!(policy.check(at(0, 0), at(0, 3600)), None);
+}
+
+#[test]
+fn never_tickets_a_car_below_the_highest_limit() {
+	let policy = TicketPolicy::new(u16::MAX);
+
+	assert_eq!(policy.check(at(0, 0), at(u16::MAX, 3600)), None);
+}
+
+#[test]
+fn covers_the_day_of_observations_on_the_same_day() {
+	let policy = TicketPolicy::new(60);
+	let speeding = policy.check(at(0, 86_400), at(1, 86_430)).unwrap();
+
+	assert_eq!(speeding.days, 1..=1);
+}
+
+#[test]
+fn covers_both_days_across_midnight() {
+	let policy = TicketPolicy::new(60);
+	let speeding = policy.check(at(0, 86_399), at(1, 86_400)).unwrap();
+
+	assert_eq!(speeding.days, 0..=1);
+}
+
+#[test]
+fn covers_every_day_of_a_span() {
+	let policy = TicketPolicy::new(1);
+	let speeding = policy.check(at(0, 0), at(u16::MAX, 5 * 86_400)).unwrap();
+
+	assert_eq!(speeding.days.collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn handles_the_latest_timestamps() {
+	let policy = TicketPolicy::new(60);
+	let speeding = policy.check(at(0, u32::MAX - 45), at(1, u32::MAX)).unwrap();
+
+	assert_eq!(speeding.speed, 8000);
+	assert_eq!(
+		speeding.days,
+		u32::MAX / SECONDS_PER_DAY..=u32::MAX / SECONDS_PER_DAY
+	);
+}
@@ -0,0 +1,102 @@
+use crate::server::MAX_CONNECTIONS;
+
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Default port of the protohackers test suite.
+const DEFAULT_PORT: u16 = 1222;
+
+/// Options every server understands.
+///
+/// Binding to an IPv6 address accepts IPv4 connections as well, unless
+/// `ipv6_only` is set. With a `metrics_port` the server exposes its metrics
+/// on that port of the same address.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub ipv6_only: bool,
+    pub max_connections: usize,
+    pub metrics_port: Option<u16>,
+}
+
+impl ServerConfig {
+    pub fn new(port: u16, max_connections: usize) -> ServerConfig {
+        ServerConfig {
+            port,
+            max_connections,
+            ..ServerConfig::default()
+        }
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    /// Binds a TCP listener to the configured address.
+    pub async fn bind(&self) -> io::Result<TcpListener> {
+        let socket = self.socket(Type::STREAM, Protocol::TCP)?;
+        socket.listen(1024)?;
+
+        TcpListener::from_std(socket.into())
+    }
+
+    /// Binds a UDP socket to the configured address.
+    pub async fn bind_udp(&self) -> io::Result<UdpSocket> {
+        let socket = self.socket(Type::DGRAM, Protocol::UDP)?;
+
+        UdpSocket::from_std(socket.into())
+    }
+
+    /// Binds the listener of the metrics endpoint, if one is configured.
+    pub async fn bind_metrics(&self) -> io::Result<Option<TcpListener>> {
+        let Some(port) = self.metrics_port else {
+            return Ok(None);
+        };
+
+        let config = ServerConfig {
+            port,
+            ..self.clone()
+        };
+
+        config.bind().await.map(Some)
+    }
+
+    fn socket(&self, ty: Type, protocol: Protocol) -> io::Result<Socket> {
+        let address = self.address();
+        let socket = Socket::new(Domain::for_address(address), ty, Some(protocol))?;
+
+        if address.is_ipv6() {
+            socket.set_only_v6(self.ipv6_only)?;
+        }
+
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&address.into())?;
+
+        Ok(socket)
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: DEFAULT_PORT,
+            ipv6_only: false,
+            max_connections: MAX_CONNECTIONS,
+            metrics_port: None,
+        }
+    }
+}
+
+impl AsMut<ServerConfig> for ServerConfig {
+    fn as_mut(&mut self) -> &mut ServerConfig {
+        self
+    }
+}
@@ -0,0 +1,144 @@
+//! Just enough HTTP/1.1 to answer the operational endpoints of a server.
+//!
+//! Every connection carries exactly one request without a body and is closed
+//! after the response.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+use tracing::{debug, error};
+
+/// Upper bound for the request line and headers, anything larger is dropped.
+const MAX_HEAD_LENGTH: usize = 8 * 1024;
+
+/// Time a client has to send the request line and headers.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause after a failed accept before the next one.
+const ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Everything after the `?` of the request target, empty if there is none.
+    pub query: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &'static str, body: String) -> Response {
+        Response {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn not_found() -> Response {
+        Response::new(404, "text/plain", "not found\n".to_string())
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "",
+        }
+    }
+}
+
+/// Accepts connections on `listener` and answers each request with `handler`,
+/// until the task is aborted. A failed accept is logged and retried.
+pub async fn serve<F, Fut>(listener: TcpListener, handler: F) -> crate::Result<()>
+where
+    F: Fn(Request) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    loop {
+        let socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(err) => {
+                // Errors like running out of file descriptors go away with time.
+                error!(cause = ?err, "failed to accept an http connection");
+                time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let handler = handler.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = respond(socket, handler).await {
+                debug!(cause = ?err, "http connection error");
+            }
+        });
+    }
+}
+
+async fn respond<F, Fut>(socket: TcpStream, handler: F) -> crate::Result<()>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Response>,
+{
+    let mut stream = BufReader::new(socket);
+    let head = time::timeout(
+        HEAD_TIMEOUT,
+        read_head((&mut stream).take(MAX_HEAD_LENGTH as u64)),
+    )
+    .await
+    .map_err(|_| "request head not sent in time")??;
+
+    let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => {
+            let (path, query) = target.split_once('?').unwrap_or((target, ""));
+            handler(Request {
+                method: method.to_string(),
+                path: path.to_string(),
+                query: query.to_string(),
+            })
+            .await
+        }
+        _ => Response::new(400, "text/plain", "bad request\n".to_string()),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len()
+    );
+
+    let socket = stream.get_mut();
+    socket.write_all(head.as_bytes()).await?;
+    socket.write_all(response.body.as_bytes()).await?;
+    socket.shutdown().await?;
+
+    Ok(())
+}
+
+/// Reads the request line and headers up to the empty line that ends them.
+/// `stream` ends after [`MAX_HEAD_LENGTH`] bytes, a longer head is refused.
+async fn read_head(mut stream: impl AsyncBufRead + Unpin) -> crate::Result<String> {
+    let mut head = String::new();
+
+    loop {
+        if stream.read_line(&mut head).await? == 0 {
+            return Err("incomplete request".into());
+        }
+        if head.ends_with("\r\n\r\n") || head.ends_with("\n\n") {
+            return Ok(head);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Shared server runtime for the protohackers solutions.
+//!
+//! Every TCP based problem needs the same plumbing: accept connections with a
+//! backoff on transient errors, limit the number of concurrent connections and
+//! shut down gracefully once a signal arrives. [`Server`] takes care of that,
+//! a problem only implements [`ConnectionHandler`] for its own protocol.
+
+mod config;
+pub use config::ServerConfig;
+
+pub mod http;
+
+pub mod metrics;
+
+pub mod server;
+pub use server::{ConnectionHandler, Server};
+
+mod shutdown;
+pub use shutdown::Shutdown;
+
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, Error>;
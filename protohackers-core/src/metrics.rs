@@ -0,0 +1,187 @@
+//! Counters and gauges rendered in the Prometheus text format.
+//!
+//! A [`Registry`] holds the metrics of one server. Metrics are cheap handles
+//! that can be cloned into every connection, [`serve`] exposes them on
+//! `GET /metrics`.
+
+use crate::http::{self, Request, Response};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+/// A value that only goes up.
+#[derive(Clone, Debug, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that goes up and down.
+#[derive(Clone, Debug, Default)]
+pub struct Gauge(Arc<AtomicI64>);
+
+impl Gauge {
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dec(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters partitioned by the value of a single label.
+#[derive(Clone, Debug, Default)]
+pub struct CounterVec(Arc<Mutex<BTreeMap<String, Counter>>>);
+
+impl CounterVec {
+    /// Returns the counter for `value`, creating it on first use.
+    pub fn with(&self, value: &str) -> Counter {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(value.to_string())
+            .or_default()
+            .clone()
+    }
+}
+
+#[derive(Debug)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+    CounterVec { label: String, counters: CounterVec },
+}
+
+#[derive(Debug)]
+struct Family {
+    name: String,
+    help: String,
+    metric: Metric,
+}
+
+/// The metrics of one server, every name is prefixed with the name of the
+/// registry.
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    prefix: String,
+    families: Arc<Mutex<Vec<Family>>>,
+}
+
+impl Registry {
+    pub fn new(prefix: &str) -> Registry {
+        Registry {
+            prefix: prefix.to_string(),
+            families: Arc::default(),
+        }
+    }
+
+    pub fn counter(&self, name: &str, help: &str) -> Counter {
+        let counter = Counter::default();
+        self.register(name, help, Metric::Counter(counter.clone()));
+        counter
+    }
+
+    pub fn gauge(&self, name: &str, help: &str) -> Gauge {
+        let gauge = Gauge::default();
+        self.register(name, help, Metric::Gauge(gauge.clone()));
+        gauge
+    }
+
+    pub fn counter_vec(&self, name: &str, help: &str, label: &str) -> CounterVec {
+        let counters = CounterVec::default();
+        self.register(
+            name,
+            help,
+            Metric::CounterVec {
+                label: label.to_string(),
+                counters: counters.clone(),
+            },
+        );
+        counters
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        for family in self.families.lock().unwrap().iter() {
+            let name = &family.name;
+            let kind = match family.metric {
+                Metric::Gauge(_) => "gauge",
+                _ => "counter",
+            };
+
+            let _ = writeln!(out, "# HELP {name} {}", family.help);
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+
+            match &family.metric {
+                Metric::Counter(counter) => {
+                    let _ = writeln!(out, "{name} {}", counter.get());
+                }
+                Metric::Gauge(gauge) => {
+                    let _ = writeln!(out, "{name} {}", gauge.get());
+                }
+                Metric::CounterVec { label, counters } => {
+                    for (value, counter) in counters.0.lock().unwrap().iter() {
+                        let _ = writeln!(out, "{name}{{{label}=\"{value}\"}} {}", counter.get());
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    fn register(&self, name: &str, help: &str, metric: Metric) {
+        let name = if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}_{name}", self.prefix)
+        };
+
+        self.families.lock().unwrap().push(Family {
+            name,
+            help: help.to_string(),
+            metric,
+        });
+    }
+}
+
+/// Serves `GET /metrics` on `listener` until the task is aborted.
+pub async fn serve(listener: TcpListener, registry: Registry) -> crate::Result<()> {
+    http::serve(listener, move |request: Request| {
+        let registry = registry.clone();
+        async move {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/metrics") => {
+                    Response::new(200, "text/plain; version=0.0.4", registry.render())
+                }
+                _ => Response::not_found(),
+            }
+        }
+    })
+    .await
+}
@@ -0,0 +1,194 @@
+use crate::metrics::{self, Counter, Gauge, Registry};
+use crate::Shutdown;
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::time::{self, Duration};
+use tracing::{error, info};
+
+/// Default upper bound of concurrently handled connections.
+pub const MAX_CONNECTIONS: usize = 100;
+
+/// The protocol specific part of a server.
+///
+/// [`Server`] calls `handle` once per accepted connection, on its own task.
+/// The handler should return once the peer disconnects or `shutdown` fires.
+pub trait ConnectionHandler: Send + Sync + 'static {
+    fn handle(
+        &self,
+        socket: TcpStream,
+        address: SocketAddr,
+        shutdown: Shutdown,
+    ) -> impl Future<Output = crate::Result<()>> + Send;
+}
+
+/// A TCP server with connection limiting, accept backoff and graceful shutdown.
+pub struct Server<H> {
+    listener: TcpListener,
+    handler: Arc<H>,
+    max_connections: usize,
+    registry: Registry,
+    metrics_listener: Option<TcpListener>,
+}
+
+struct Listener<H> {
+    listener: TcpListener,
+    handler: Arc<H>,
+    limit_connections: Arc<Semaphore>,
+    metrics: ListenerMetrics,
+    notify_shutdown: broadcast::Sender<()>,
+    shutdown_complete_tx: mpsc::Sender<()>,
+}
+
+#[derive(Clone)]
+struct ListenerMetrics {
+    connections_active: Gauge,
+    connections_accepted: Counter,
+}
+
+impl<H: ConnectionHandler> Server<H> {
+    pub fn new(listener: TcpListener, handler: H) -> Server<H> {
+        Server {
+            listener,
+            handler: Arc::new(handler),
+            max_connections: MAX_CONNECTIONS,
+            registry: Registry::default(),
+            metrics_listener: None,
+        }
+    }
+
+    /// Sets how many connections are handled at the same time. Further
+    /// connections wait in the accept backlog until a slot frees up.
+    pub fn max_connections(mut self, max_connections: usize) -> Server<H> {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Records the connection metrics in `registry` and serves all of its
+    /// metrics on `listener`, if there is one.
+    pub fn metrics(mut self, registry: Registry, listener: Option<TcpListener>) -> Server<H> {
+        self.registry = registry;
+        self.metrics_listener = listener;
+        self
+    }
+
+    /// Accepts connections until `shutdown` completes, then waits for all
+    /// active connections to finish.
+    pub async fn run(self, shutdown: impl Future) -> crate::Result<()> {
+        let (notify_shutdown, _) = broadcast::channel(1);
+        let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel(1);
+
+        let connections_limit = self.registry.gauge(
+            "connections_limit",
+            "Maximum number of connections handled at the same time.",
+        );
+        connections_limit.set(self.max_connections as i64);
+
+        let metrics = ListenerMetrics {
+            connections_active: self
+                .registry
+                .gauge("connections_active", "Connections currently handled."),
+            connections_accepted: self
+                .registry
+                .counter("connections_accepted_total", "Connections accepted."),
+        };
+
+        let metrics_server = self.metrics_listener.map(|listener| {
+            info!(address = ?listener.local_addr(), "serving metrics");
+            tokio::spawn(metrics::serve(listener, self.registry.clone()))
+        });
+
+        let mut server = Listener {
+            listener: self.listener,
+            handler: self.handler,
+            limit_connections: Arc::new(Semaphore::new(self.max_connections)),
+            metrics,
+            notify_shutdown,
+            shutdown_complete_tx,
+        };
+
+        tokio::select! {
+            res = server.run() => {
+                if let Err(err) = res {
+                    error!(cause = %err, "failed to accept");
+                }
+            }
+            _ = shutdown => {
+                info!("shutting down");
+            }
+        }
+
+        let Listener {
+            shutdown_complete_tx,
+            notify_shutdown,
+            ..
+        } = server;
+
+        drop(notify_shutdown);
+        drop(shutdown_complete_tx);
+
+        let _ = shutdown_complete_rx.recv().await;
+
+        if let Some(metrics_server) = metrics_server {
+            metrics_server.abort();
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: ConnectionHandler> Listener<H> {
+    async fn run(&mut self) -> crate::Result<()> {
+        info!("accepting inbound connections");
+
+        loop {
+            let permit = self
+                .limit_connections
+                .clone()
+                .acquire_owned()
+                .await
+                .unwrap();
+
+            let (socket, address) = self.accept().await?;
+
+            let handler = self.handler.clone();
+            let metrics = self.metrics.clone();
+            let shutdown = Shutdown::new(self.notify_shutdown.subscribe());
+            let shutdown_complete = self.shutdown_complete_tx.clone();
+
+            metrics.connections_accepted.inc();
+            metrics.connections_active.inc();
+
+            tokio::spawn(async move {
+                if let Err(err) = handler.handle(socket, address, shutdown).await {
+                    error!(cause = ?err, "connection error");
+                }
+                metrics.connections_active.dec();
+                drop(permit);
+                drop(shutdown_complete);
+            });
+        }
+    }
+
+    async fn accept(&mut self) -> crate::Result<(TcpStream, SocketAddr)> {
+        let mut backoff = 1;
+
+        loop {
+            match self.listener.accept().await {
+                Ok(accepted) => return Ok(accepted),
+                Err(err) => {
+                    if backoff > 64 {
+                        return Err(err.into());
+                    }
+                }
+            }
+
+            time::sleep(Duration::from_secs(backoff)).await;
+
+            backoff *= 2;
+        }
+    }
+}
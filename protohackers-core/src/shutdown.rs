@@ -1,7 +1,12 @@
 use tokio::sync::broadcast;
+use tracing::debug;
 
+/// Listens for the server shutdown signal.
+///
+/// Each connection gets its own `Shutdown` and is expected to stop processing
+/// once [`Shutdown::recv`] returns.
 #[derive(Debug)]
-pub(crate) struct Shutdown {
+pub struct Shutdown {
     shutdown: bool,
     notify: broadcast::Receiver<()>,
 }
@@ -14,15 +19,17 @@ impl Shutdown {
         }
     }
 
-    pub(crate) fn is_shutdown(&self) -> bool {
+    /// Returns `true` if the shutdown signal has been received.
+    pub fn is_shutdown(&self) -> bool {
         self.shutdown
     }
 
-    pub(crate) async fn recv(&mut self) {
+    /// Receive the shutdown notice, waiting if necessary.
+    pub async fn recv(&mut self) {
         if self.shutdown {
             return;
         }
-
+        debug!("waiting for shutdown");
         let _ = self.notify.recv().await;
 
         self.shutdown = true;
@@ -0,0 +1,247 @@
+//! Helpers to run a server in-process and hold scripted conversations with it.
+//!
+//! A [`TestServer`] hands a listener on an ephemeral port to the `run`
+//! function of a problem and stops it again through the shutdown future. The
+//! clients panic on unexpected answers, so a test reads like the example
+//! sessions in the problem statements.
+//!
+//! ```ignore
+//! let server = TestServer::start(|listener, shutdown| {
+//!     problem_00::server::run(listener, ServerConfig::default(), shutdown)
+//! })
+//! .await;
+//!
+//! let mut client = BinaryClient::connect(server.address()).await;
+//! client.send(b"hello").await;
+//! client.expect(b"hello").await;
+//!
+//! server.stop().await.unwrap();
+//! ```
+
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration};
+
+/// How long a client waits for an answer before the test fails.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A server running on its own task until [`TestServer::stop`] is called.
+pub struct TestServer {
+    address: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<crate::Result<()>>,
+}
+
+impl TestServer {
+    /// Binds a TCP listener on `127.0.0.1:0` and spawns `run` with it.
+    pub async fn start<F, Fut>(run: F) -> TestServer
+    where
+        F: FnOnce(TcpListener, oneshot::Receiver<()>) -> Fut,
+        Fut: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        TestServer::spawn(address, |shutdown| run(listener, shutdown))
+    }
+
+    /// Binds a UDP socket on `127.0.0.1:0` and spawns `run` with it.
+    pub async fn start_udp<F, Fut>(run: F) -> TestServer
+    where
+        F: FnOnce(UdpSocket, oneshot::Receiver<()>) -> Fut,
+        Fut: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        TestServer::spawn(address, |shutdown| run(socket, shutdown))
+    }
+
+    fn spawn<F, Fut>(address: SocketAddr, run: F) -> TestServer
+    where
+        F: FnOnce(oneshot::Receiver<()>) -> Fut,
+        Fut: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        let (shutdown, notify) = oneshot::channel();
+
+        TestServer {
+            address,
+            shutdown,
+            task: tokio::spawn(run(notify)),
+        }
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Triggers the shutdown future and waits for the server to return.
+    pub async fn stop(self) -> crate::Result<()> {
+        let _ = self.shutdown.send(());
+
+        time::timeout(TIMEOUT, self.task)
+            .await
+            .map_err(|_| "server did not shut down in time")??
+    }
+}
+
+/// A client for protocols with one message per `\n` terminated line.
+pub struct LineClient {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl LineClient {
+    pub async fn connect(address: SocketAddr) -> LineClient {
+        let (read, writer) = TcpStream::connect(address).await.unwrap().into_split();
+
+        LineClient {
+            reader: BufReader::new(read),
+            writer,
+        }
+    }
+
+    /// Sends `line` followed by a newline.
+    pub async fn send(&mut self, line: &str) {
+        self.writer
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Receives the next line without its newline.
+    pub async fn recv(&mut self) -> String {
+        let mut line = String::new();
+        let n = time::timeout(TIMEOUT, self.reader.read_line(&mut line))
+            .await
+            .expect("timed out waiting for a line")
+            .unwrap();
+
+        assert!(n > 0, "connection closed while waiting for a line");
+        assert!(line.ends_with('\n'), "connection closed mid line: {line:?}");
+
+        line.pop();
+        line
+    }
+
+    pub async fn expect(&mut self, line: &str) {
+        assert_eq!(self.recv().await, line);
+    }
+
+    /// Closes the sending half, the server sees the end of the stream.
+    pub async fn close(&mut self) {
+        self.writer.shutdown().await.unwrap();
+    }
+
+    /// Asserts that the server closes the connection without sending more.
+    pub async fn expect_closed(&mut self) {
+        let mut rest = String::new();
+        let n = time::timeout(TIMEOUT, self.reader.read_line(&mut rest))
+            .await
+            .expect("timed out waiting for the connection to close")
+            .unwrap_or(0);
+
+        assert_eq!(n, 0, "expected the connection to close, got {rest:?}");
+    }
+}
+
+/// A client for byte oriented protocols.
+pub struct BinaryClient {
+    stream: TcpStream,
+}
+
+impl BinaryClient {
+    pub async fn connect(address: SocketAddr) -> BinaryClient {
+        BinaryClient {
+            stream: TcpStream::connect(address).await.unwrap(),
+        }
+    }
+
+    /// The address the server sees the client connect from.
+    pub fn local_address(&self) -> SocketAddr {
+        self.stream.local_addr().unwrap()
+    }
+
+    pub async fn send(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).await.unwrap();
+    }
+
+    /// Receives exactly `n` bytes.
+    pub async fn recv(&mut self, n: usize) -> Vec<u8> {
+        let mut buf = vec![0; n];
+        time::timeout(TIMEOUT, self.stream.read_exact(&mut buf))
+            .await
+            .unwrap_or_else(|_| panic!("timed out waiting for {n} bytes"))
+            .unwrap();
+
+        buf
+    }
+
+    pub async fn expect(&mut self, bytes: &[u8]) {
+        assert_eq!(self.recv(bytes.len()).await, bytes);
+    }
+
+    /// Closes the sending half, the server sees the end of the stream.
+    pub async fn close(&mut self) {
+        self.stream.shutdown().await.unwrap();
+    }
+
+    /// Asserts that the server closes the connection without sending more.
+    pub async fn expect_closed(&mut self) {
+        let mut rest = Vec::new();
+        time::timeout(TIMEOUT, self.stream.read_to_end(&mut rest))
+            .await
+            .expect("timed out waiting for the connection to close")
+            .unwrap_or(0);
+
+        assert!(
+            rest.is_empty(),
+            "expected the connection to close, got {rest:?}"
+        );
+    }
+
+    /// Asserts that the server sends nothing for `duration`.
+    pub async fn expect_silence(&mut self, duration: Duration) {
+        let mut buf = [0; 1];
+        if let Ok(res) = time::timeout(duration, self.stream.read(&mut buf)).await {
+            panic!("expected silence, got {res:?}");
+        }
+    }
+}
+
+/// A client for datagram protocols, one message per packet.
+pub struct UdpClient {
+    socket: UdpSocket,
+}
+
+impl UdpClient {
+    pub async fn connect(address: SocketAddr) -> UdpClient {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        socket.connect(address).await.unwrap();
+
+        UdpClient { socket }
+    }
+
+    pub async fn send(&self, message: &str) {
+        self.socket.send(message.as_bytes()).await.unwrap();
+    }
+
+    pub async fn recv(&self) -> String {
+        let mut buf = [0; 1024];
+        let n = time::timeout(TIMEOUT, self.socket.recv(&mut buf))
+            .await
+            .expect("timed out waiting for a packet")
+            .unwrap();
+
+        String::from_utf8(buf[..n].to_vec()).unwrap()
+    }
+
+    pub async fn expect(&self, message: &str) {
+        assert_eq!(self.recv().await, message);
+    }
+}
@@ -0,0 +1,76 @@
+use protohackers_core::metrics::{self, Registry};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+fn registry() -> Registry {
+    let registry = Registry::new("test");
+
+    registry.counter("requests", "Requests served.").inc_by(3);
+    registry.gauge("active", "Requests in flight.").set(2);
+    let errors = registry.counter_vec("errors", "Errors by kind.", "kind");
+    errors.with("timeout").inc();
+    errors.with("reset").inc_by(2);
+
+    registry
+}
+
+const RENDERED: &str = "\
+# HELP test_requests Requests served.
+# TYPE test_requests counter
+test_requests 3
+# HELP test_active Requests in flight.
+# TYPE test_active gauge
+test_active 2
+# HELP test_errors Errors by kind.
+# TYPE test_errors counter
+test_errors{kind=\"reset\"} 2
+test_errors{kind=\"timeout\"} 1
+";
+
+async fn get(registry: Registry, path: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = tokio::spawn(metrics::serve(listener, registry));
+
+    let mut socket = TcpStream::connect(address).await.unwrap();
+    socket
+        .write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+
+    server.abort();
+    response
+}
+
+#[test]
+fn renders_every_metric_with_its_help_and_type() {
+    assert_eq!(registry().render(), RENDERED);
+}
+
+#[test]
+fn renders_an_empty_registry_as_nothing() {
+    assert_eq!(Registry::new("test").render(), "");
+}
+
+#[tokio::test]
+async fn serves_the_metrics_over_http() {
+    let response = get(registry(), "/metrics").await;
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+    assert!(head.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+    assert!(head.contains(&format!("Content-Length: {}\r\n", RENDERED.len())));
+    assert_eq!(body, RENDERED);
+}
+
+#[tokio::test]
+async fn answers_other_paths_with_not_found() {
+    let response = get(registry(), "/other").await;
+
+    assert!(
+        response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+        "{response}"
+    );
+}
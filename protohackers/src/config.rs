@@ -0,0 +1,221 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use clap::{Args, ValueEnum};
+use protohackers_core::ServerConfig;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::problem::Problem;
+
+/// Server options from the command line or the environment, they take
+/// precedence over the config file.
+#[derive(Args, Clone, Debug, Default)]
+pub(crate) struct ServerArgs {
+    /// Address to listen on, `::` accepts IPv4 and IPv6 connections
+    #[arg(long, env = "PROTOHACKERS_IP")]
+    pub(crate) ip: Option<IpAddr>,
+    /// Port to listen on, defaults to the port of the problem
+    #[arg(long, env = "PROTOHACKERS_PORT")]
+    pub(crate) port: Option<u16>,
+    /// Whether an IPv6 `ip` refuses IPv4 connections
+    #[arg(long, env = "PROTOHACKERS_IPV6_ONLY")]
+    pub(crate) ipv6_only: Option<bool>,
+    /// Maximum number of connections handled at the same time
+    #[arg(long, env = "PROTOHACKERS_MAX_CONNECTIONS")]
+    pub(crate) max_connections: Option<usize>,
+    /// Serve Prometheus metrics on `GET /metrics` of this port
+    #[arg(long, env = "PROTOHACKERS_METRICS_PORT")]
+    pub(crate) metrics_port: Option<u16>,
+}
+
+impl ServerArgs {
+    fn apply(&self, config: &mut ServerConfig) {
+        if let Some(ip) = self.ip {
+            config.ip = ip;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(ipv6_only) = self.ipv6_only {
+            config.ipv6_only = ipv6_only;
+        }
+        if let Some(max_connections) = self.max_connections {
+            config.max_connections = max_connections;
+        }
+        if let Some(metrics_port) = self.metrics_port {
+            config.metrics_port = Some(metrics_port);
+        }
+    }
+}
+
+/// Settings read from a TOML file, with one table per problem:
+///
+/// ```toml
+/// [speed-daemon]
+/// ip = "::"
+/// port = 1222
+/// max_connections = 1500
+/// metrics_port = 9222
+/// journal = "speed-daemon.jsonl"
+/// dispatch = "round-robin"
+/// retention_days = 2
+/// admin_port = 9223
+/// admin_ip = "127.0.0.1"
+/// audit_log = "tickets.jsonl"
+/// idle_timeout_secs = 600
+///
+/// [budget-chat]
+/// max_name_length = 32
+/// name_chars = "-_"
+/// replay_history = true
+/// on_lag = "disconnect"
+///
+/// [mob-in-the-middle]
+/// upstream = "206.189.113.124:16963"
+/// ```
+#[derive(Debug, Default)]
+pub(crate) struct ConfigFile {
+    problems: toml::Table,
+}
+
+impl ConfigFile {
+    pub(crate) fn load(path: Option<&Path>) -> crate::Result<ConfigFile> {
+        let Some(path) = path else {
+            return Ok(ConfigFile::default());
+        };
+
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read config file {}: {err}", path.display()))?;
+        ConfigFile::parse(&content)
+    }
+
+    fn parse(content: &str) -> crate::Result<ConfigFile> {
+        let problems: toml::Table = content.parse()?;
+
+        for (name, section) in problems.iter() {
+            Problem::from_str(name, false)
+                .map_err(|_| format!("unknown problem `{name}` in config file"))?;
+
+            if !section.is_table() {
+                return Err(format!("`{name}` in config file must be a table").into());
+            }
+        }
+
+        Ok(ConfigFile { problems })
+    }
+
+    /// Builds the config of `problem` from its `defaults`, overridden by the
+    /// table of the problem in the file, overridden by `args`.
+    pub(crate) fn resolve<C>(
+        &self,
+        problem: Problem,
+        defaults: C,
+        args: &ServerArgs,
+    ) -> crate::Result<C>
+    where
+        C: Serialize + DeserializeOwned + AsMut<ServerConfig>,
+    {
+        let mut config = match toml::Value::try_from(defaults)? {
+            toml::Value::Table(table) => table,
+            _ => unreachable!("configs serialize to a table"),
+        };
+
+        if let Some(toml::Value::Table(section)) = self.problems.get(&problem.to_string()) {
+            config.extend(section.clone());
+        }
+
+        let mut config: C = config.try_into()?;
+        args.apply(config.as_mut());
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv6Addr};
+
+    use super::*;
+
+    const FILE: &str = r#"
+        [speed-daemon]
+        ip = "::"
+        port = 2000
+        ipv6_only = true
+        max_connections = 10
+        retention_days = 3
+    "#;
+
+    fn resolve(args: &ServerArgs) -> problem_06::Config {
+        ConfigFile::parse(FILE)
+            .unwrap()
+            .resolve(Problem::SpeedDaemon, problem_06::Config::default(), args)
+            .unwrap()
+    }
+
+    #[test]
+    fn the_file_overrides_the_defaults() {
+        let config = resolve(&ServerArgs::default());
+
+        assert_eq!(config.server.ip, IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        assert_eq!(config.server.port, 2000);
+        assert!(config.server.ipv6_only);
+        assert_eq!(config.server.max_connections, 10);
+        assert_eq!(config.server.metrics_port, None);
+        assert_eq!(config.retention_days, Some(3));
+        assert_eq!(
+            config.compaction_interval_secs,
+            problem_06::Config::default().compaction_interval_secs
+        );
+    }
+
+    #[test]
+    fn the_arguments_override_the_file() {
+        let config = resolve(&ServerArgs {
+            ip: Some(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+            port: Some(3000),
+            ipv6_only: Some(false),
+            max_connections: Some(20),
+            metrics_port: Some(9000),
+        });
+
+        assert_eq!(config.server.ip, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(config.server.port, 3000);
+        assert!(!config.server.ipv6_only);
+        assert_eq!(config.server.max_connections, 20);
+        assert_eq!(config.server.metrics_port, Some(9000));
+        assert_eq!(config.retention_days, Some(3));
+    }
+
+    #[test]
+    fn a_problem_without_a_table_keeps_its_defaults() {
+        let config = ConfigFile::parse(FILE)
+            .unwrap()
+            .resolve(
+                Problem::BudgetChat,
+                problem_03::Config::default(),
+                &ServerArgs {
+                    ipv6_only: Some(true),
+                    ..ServerArgs::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            config,
+            problem_03::Config {
+                server: ServerConfig {
+                    ipv6_only: true,
+                    ..problem_03::Config::default().server
+                },
+                ..problem_03::Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn refuses_unknown_problems() {
+        assert!(ConfigFile::parse("[speed-demon]\nport = 2000\n").is_err());
+    }
+}
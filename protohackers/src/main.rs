@@ -0,0 +1,126 @@
+mod config;
+mod problem;
+
+use config::{ConfigFile, ServerArgs};
+use problem::{Instance, Problem};
+
+use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::signal;
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Runs the protohackers servers from a single binary.
+#[derive(Debug, Parser)]
+#[command(name = "protohackers", version)]
+struct Cli {
+    /// TOML file with a table of settings per problem
+    #[arg(long, global = true, env = "PROTOHACKERS_CONFIG")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Start the server for one problem
+    Serve {
+        problem: Problem,
+        #[command(flatten)]
+        args: ServerArgs,
+    },
+    /// Start several problems at once, e.g. `speed-daemon=1222 budget-chat=1223`
+    ServeMany {
+        /// `<PROBLEM>[=<PORT>]`, one per server
+        #[arg(required = true)]
+        servers: Vec<Instance>,
+    },
+}
+
+#[tokio::main]
+pub async fn main() -> Result<()> {
+    tracing_subscriber::fmt::try_init()?;
+
+    let cli = Cli::parse();
+    let config_file = Arc::new(ConfigFile::load(cli.config.as_deref())?);
+
+    let instances = match cli.command {
+        Command::Serve { problem, args } => vec![(problem, args)],
+        Command::ServeMany { servers } => servers
+            .into_iter()
+            .map(|Instance { problem, port }| {
+                let args = ServerArgs {
+                    port,
+                    ..ServerArgs::default()
+                };
+                (problem, args)
+            })
+            .collect(),
+    };
+    check_ports(&config_file, &instances)?;
+
+    let (notify_shutdown, _) = broadcast::channel::<()>(1);
+    let mut servers = JoinSet::new();
+
+    for (problem, args) in instances {
+        let mut shutdown = notify_shutdown.subscribe();
+        let config_file = config_file.clone();
+
+        servers.spawn(async move {
+            info!("Starting {problem}");
+            let shutdown = async move {
+                let _ = shutdown.recv().await;
+            };
+            (problem, problem.serve(&config_file, &args, shutdown).await)
+        });
+    }
+
+    tokio::spawn(async move {
+        let _ = signal::ctrl_c().await;
+        drop(notify_shutdown);
+    });
+
+    let mut failed = false;
+
+    while let Some(res) = servers.join_next().await {
+        match res? {
+            (problem, Ok(())) => info!("{problem} stopped"),
+            (problem, Err(err)) => {
+                error!(cause = %err, "{problem} failed");
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err("at least one server failed".into());
+    }
+
+    Ok(())
+}
+
+/// Refuses to start servers that would listen on the same port, before any
+/// of them binds it.
+fn check_ports(config_file: &ConfigFile, instances: &[(Problem, ServerArgs)]) -> Result<()> {
+    let mut ports = HashMap::new();
+
+    for (problem, args) in instances {
+        let config = problem.server_config(config_file, args)?;
+        for port in [Some(config.port), config.metrics_port]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(other) = ports.insert(port, problem) {
+                return Err(format!("{other} and {problem} both listen on port {port}").into());
+            }
+        }
+    }
+
+    Ok(())
+}
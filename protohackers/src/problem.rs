@@ -0,0 +1,128 @@
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use protohackers_core::ServerConfig;
+
+use crate::config::{ConfigFile, ServerArgs};
+
+/// The problems this binary knows how to serve, named after the
+/// protohackers problem titles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Problem {
+    SmokeTest,
+    PrimeTime,
+    MeansToAnEnd,
+    BudgetChat,
+    UnusualDatabase,
+    MobInTheMiddle,
+    SpeedDaemon,
+}
+
+/// A problem together with the port it should listen on.
+#[derive(Clone, Debug)]
+pub(crate) struct Instance {
+    pub(crate) problem: Problem,
+    pub(crate) port: Option<u16>,
+}
+
+impl Problem {
+    /// The server options of the problem before the config file and the
+    /// arguments override them.
+    fn server_defaults(self) -> ServerConfig {
+        match self {
+            Problem::SmokeTest => {
+                ServerConfig::new(problem_00::DEFAULT_PORT, problem_00::MAX_CONNECTIONS)
+            }
+            Problem::PrimeTime => {
+                ServerConfig::new(problem_01::DEFAULT_PORT, problem_01::MAX_CONNECTIONS)
+            }
+            Problem::MeansToAnEnd => {
+                ServerConfig::new(problem_02::DEFAULT_PORT, problem_02::MAX_CONNECTIONS)
+            }
+            Problem::BudgetChat => problem_03::Config::default().server,
+            Problem::UnusualDatabase => ServerConfig::new(problem_04::DEFAULT_PORT, 0),
+            Problem::MobInTheMiddle => problem_05::Config::default().server,
+            Problem::SpeedDaemon => problem_06::Config::default().server,
+        }
+    }
+
+    /// The server options [`Problem::serve`] binds with.
+    pub(crate) fn server_config(
+        self,
+        config_file: &ConfigFile,
+        args: &ServerArgs,
+    ) -> crate::Result<ServerConfig> {
+        config_file.resolve(self, self.server_defaults(), args)
+    }
+
+    /// Binds the listening socket and runs the server until `shutdown` completes.
+    pub(crate) async fn serve(
+        self,
+        config_file: &ConfigFile,
+        args: &ServerArgs,
+        shutdown: impl Future,
+    ) -> crate::Result<()> {
+        match self {
+            Problem::SmokeTest => {
+                let config = self.server_config(config_file, args)?;
+                problem_00::server::run(config.bind().await?, config, shutdown).await
+            }
+            Problem::PrimeTime => {
+                let config = self.server_config(config_file, args)?;
+                problem_01::server::run(config.bind().await?, config, shutdown).await
+            }
+            Problem::MeansToAnEnd => {
+                let config = self.server_config(config_file, args)?;
+                problem_02::server::run(config.bind().await?, config, shutdown).await
+            }
+            Problem::BudgetChat => {
+                let config = config_file.resolve(self, problem_03::Config::default(), args)?;
+                problem_03::server::run(config.server.bind().await?, config, shutdown).await
+            }
+            Problem::UnusualDatabase => {
+                let config = self.server_config(config_file, args)?;
+                problem_04::server::run(config.bind_udp().await?, config, shutdown).await
+            }
+            Problem::MobInTheMiddle => {
+                let config = config_file.resolve(self, problem_05::Config::default(), args)?;
+                problem_05::server::run(config.server.bind().await?, config, shutdown).await
+            }
+            Problem::SpeedDaemon => {
+                let config = config_file.resolve(self, problem_06::Config::default(), args)?;
+                problem_06::server::run(config.server.bind().await?, config, shutdown).await
+            }
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => value.get_name().fmt(fmt),
+            None => fmt::Debug::fmt(self, fmt),
+        }
+    }
+}
+
+impl FromStr for Instance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Instance, String> {
+        let (name, port) = match s.split_once('=') {
+            Some((name, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|err| format!("invalid port `{port}`: {err}"))?;
+                (name, Some(port))
+            }
+            None => (s, None),
+        };
+
+        Ok(Instance {
+            problem: Problem::from_str(name, true)?,
+            port,
+        })
+    }
+}